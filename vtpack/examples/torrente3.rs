@@ -1,15 +1,11 @@
-use std::fs::File;
-use std::io::BufReader;
-use vtpack::VtPackFile;
+use vtpack::VtPackArchive;
 
 fn main() {
-    let vtpack_file = File::open("torrent3.vpk").unwrap();
-    let mut vtpack_reader = BufReader::new(&vtpack_file);
-    let vtpack = VtPackFile::new(&mut vtpack_reader).unwrap();
+    let mut vtpack = VtPackArchive::open_path("torrent3.vpk").unwrap();
 
-    for entry in vtpack.list_entries() {
+    for entry in vtpack.entries() {
         println!("> {} (file: {:?})", entry.get_path(), entry.is_file());
     }
 
-    vtpack.export_all(&mut vtpack_reader, "tor3_vpk_out");
-}
\ No newline at end of file
+    vtpack.extract_to("tor3_vpk_out").unwrap();
+}
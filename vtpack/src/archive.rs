@@ -0,0 +1,150 @@
+use std::{fs::File, io::{BufReader, Read, Seek}, path::Path};
+use crate::{VtPackFile, VtPackProcessedEntry, VtPackEntryFilter, VtPackExtractOptions, VtPackExtractReport, VtPackEntryReader, VtPackRawEntryReader, VtPackIntegrityCheck, VtPackTimestampDecoder, VtPackProfile, VtPackProfileRegistry, VtPackReadOptions, VtPackError, Result, IoResultExt};
+
+pub struct VtPackArchive<R: Read + Seek> {
+    reader: R,
+    file: VtPackFile,
+    ignore_case: bool,
+    integrity_check: Option<VtPackIntegrityCheck>,
+    profile_name: Option<String>
+}
+
+impl<R: Read + Seek> VtPackArchive<R> {
+    pub fn new(reader: R) -> Result<Self> {
+        Self::new_with_options(reader, &VtPackReadOptions::default())
+    }
+
+    pub fn new_with_options(mut reader: R, options: &VtPackReadOptions) -> Result<Self> {
+        // All offsets in the format are absolute
+        reader.rewind()?;
+        let file = VtPackFile::new_with_options(&mut reader, options)?;
+        Ok(Self {
+            reader,
+            file,
+            ignore_case: false,
+            integrity_check: None,
+            profile_name: None
+        })
+    }
+
+    pub fn with_profile(reader: R, profile: &VtPackProfile) -> Result<Self> {
+        let mut archive = Self::new(reader)?;
+        archive.apply_profile(profile)?;
+        Ok(archive)
+    }
+
+    // Archives not matching any profile are opened with the default settings
+    pub fn detect_profile(mut reader: R, registry: &VtPackProfileRegistry) -> Result<Self> {
+        match registry.detect_from_reader(&mut reader)? {
+            Some(profile) => Self::with_profile(reader, profile),
+            None => Self::new(reader)
+        }
+    }
+
+    pub fn apply_profile(&mut self, profile: &VtPackProfile) -> Result<()> {
+        self.file.apply_profile(profile)?;
+        self.ignore_case = profile.get_ignore_case();
+        self.integrity_check = profile.get_integrity_check();
+        self.profile_name = Some(profile.get_name().clone());
+        Ok(())
+    }
+
+    pub fn get_profile_name(&self) -> Option<&String> {
+        self.profile_name.as_ref()
+    }
+
+    pub fn get_file(&self) -> &VtPackFile {
+        &self.file
+    }
+
+    pub fn get_file_and_reader(&mut self) -> (&VtPackFile, &mut R) {
+        (&self.file, &mut self.reader)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    pub fn entries(&self) -> &Vec<VtPackProcessedEntry> {
+        self.file.list_entries()
+    }
+
+    pub fn set_ignore_case(&mut self, ignore_case: bool) {
+        self.ignore_case = ignore_case;
+    }
+
+    pub fn set_timestamp_decoder(&mut self, decoder: Option<VtPackTimestampDecoder>) {
+        self.file.set_timestamp_decoder(decoder);
+    }
+
+    // Entries read as a whole get verified against this
+    pub fn set_integrity_check(&mut self, check: Option<VtPackIntegrityCheck>) {
+        self.integrity_check = check;
+    }
+
+    pub fn verify_integrity(&mut self) -> Result<Vec<usize>> {
+        match self.integrity_check {
+            Some(check) => self.file.verify_integrity(&mut self.reader, &check),
+            None => Ok(Vec::new())
+        }
+    }
+
+    fn find_entry_index(&self, path: &str) -> Result<usize> {
+        self.file.find_index(path, self.ignore_case).ok_or_else(|| VtPackError::EntryNotFound(path.to_string()))
+    }
+
+    pub fn open<S: AsRef<str>>(&mut self, path: S) -> Result<VtPackEntryReader<'_, R>> {
+        let entry_idx = self.find_entry_index(path.as_ref())?;
+        self.file.open_entry(&mut self.reader, &self.file.list_entries()[entry_idx])
+    }
+
+    pub fn open_raw<S: AsRef<str>>(&mut self, path: S) -> Result<VtPackRawEntryReader<'_, R>> {
+        let entry_idx = self.find_entry_index(path.as_ref())?;
+        self.file.open_entry_raw(&mut self.reader, &self.file.list_entries()[entry_idx])
+    }
+
+    pub fn read_raw<S: AsRef<str>>(&mut self, path: S) -> Result<Vec<u8>> {
+        let mut raw_reader = self.open_raw(path)?;
+        let mut data = Vec::with_capacity(raw_reader.get_size() as usize);
+        raw_reader.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    pub fn read<S: AsRef<str>>(&mut self, path: S) -> Result<Vec<u8>> {
+        let entry_idx = self.find_entry_index(path.as_ref())?;
+        let entry = &self.file.list_entries()[entry_idx];
+        let mut entry_reader = self.file.open_entry(&mut self.reader, entry)?;
+        let mut data = Vec::with_capacity(entry_reader.get_size() as usize);
+        entry_reader.read_to_end(&mut data)?;
+
+        if let Some(check) = self.integrity_check {
+            let actual = check.compute_path(entry).unwrap_or_else(|| check.algorithm.compute(&data));
+            self.file.check_integrity_value(entry_idx, &check, actual)?;
+        }
+        Ok(data)
+    }
+
+    pub fn extract_entry_to<P: AsRef<Path>>(&mut self, entry_idx: usize, out_path: P) -> Result<()> {
+        self.file.save_entry(&mut self.reader, &self.file.list_entries()[entry_idx], out_path)
+    }
+
+    pub fn extract_with_options<P: AsRef<Path>>(&mut self, out_path: P, filter: &VtPackEntryFilter, options: &VtPackExtractOptions) -> Result<VtPackExtractReport> {
+        self.file.export(&mut self.reader, out_path, filter, options)
+    }
+
+    pub fn extract_filtered_to<P: AsRef<Path>>(&mut self, out_path: P, filter: &VtPackEntryFilter) -> Result<VtPackExtractReport> {
+        self.file.export_filtered(&mut self.reader, out_path, filter)
+    }
+
+    pub fn extract_to<P: AsRef<Path>>(&mut self, out_path: P) -> Result<VtPackExtractReport> {
+        self.file.export_all(&mut self.reader, out_path)
+    }
+}
+
+impl VtPackArchive<BufReader<File>> {
+    pub fn open_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let f = File::open(path).with_path(path)?;
+        Self::new(BufReader::new(f))
+    }
+}
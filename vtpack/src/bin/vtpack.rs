@@ -0,0 +1,531 @@
+use std::{fs::File, io::{self, BufReader, Read, Write}, path::{Path, PathBuf}, process::ExitCode};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use vtpack::{VtPackArchive, VtPackFile, VtPackError, VtPackVersion, VtPackEntryFilter, VtPackEntryKind, VtPackPattern, VtPackExtractOptions, VtPackExtractAction, VtPackOverwritePolicy, VtPackUnknownFieldAnalyzer, VtPackChecksumTester, VtPackTimestampDecoder, VtPackTimestampField, VtPackProfileRegistry, VtPackReadOptions, VtPackStreamArchive, Result};
+
+#[derive(Parser)]
+#[command(name = "vtpack", version, about = "Inspect and extract vtPack archives")]
+struct Cli {
+    /// Game profile to open archives with, instead of detecting it
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Refuse archives with an unknown version instead of reading them as the closest known one
+    #[arg(long, global = true)]
+    strict: bool,
+    #[command(subcommand)]
+    command: Command
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List all entries with their sizes and data offsets
+    List {
+        archive: PathBuf,
+        /// Print the entries as JSON
+        #[arg(long)]
+        json: bool
+    },
+    /// Show header fields and entry counts
+    Info {
+        archive: PathBuf
+    },
+    /// Extract entries to a directory
+    Extract {
+        /// Archive to extract, or - to read it from standard input
+        archive: PathBuf,
+        /// Output directory (the current one if not given, which can't be used with --clean)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Only extract entries at or below these paths
+        paths: Vec<String>,
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Delete the whole output directory before extracting
+        #[arg(long, requires = "output")]
+        clean: bool,
+        /// What to do with files that already exist in the output directory
+        #[arg(long, value_enum, default_value_t = OnConflict::Overwrite)]
+        on_conflict: OnConflict,
+        /// Only report what would be written
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+        /// Restore file modification times, decoding this field as a FILETIME
+        #[arg(long, value_enum, value_name = "FIELD")]
+        timestamps: Option<TimestampField>,
+        /// Extract with this many worker threads (0 uses all available cores)
+        #[arg(short, long, value_name = "N")]
+        jobs: Option<usize>,
+        /// Read the archive in a single forward pass, in data offset order (faster on hard drives and network mounts)
+        #[arg(long, conflicts_with = "jobs")]
+        ordered: bool,
+        /// Write the data as stored, without decompressing anything
+        #[arg(long)]
+        raw: bool
+    },
+    /// Check the archive structure for inconsistencies
+    Verify {
+        archive: PathBuf
+    },
+    /// Collect statistics on the unknown header/entry fields of one or more archives
+    Analyze {
+        #[arg(required = true)]
+        archives: Vec<PathBuf>,
+        /// Print the statistics as JSON
+        #[arg(long)]
+        json: bool
+    },
+    /// Test whether any unknown entry field is a checksum of the entry data or path
+    Checksums {
+        #[arg(required = true)]
+        archives: Vec<PathBuf>,
+        /// Only hash entry paths, not their data
+        #[arg(long)]
+        paths_only: bool,
+        /// Also show hypotheses that don't match every file
+        #[arg(short, long)]
+        all: bool
+    },
+    /// Write the archive back out from its parsed contents, checking that nothing changed
+    Repack {
+        archive: PathBuf,
+        output: PathBuf
+    },
+    /// Write a single entry to stdout
+    Cat {
+        archive: PathBuf,
+        path: String,
+        /// Match the entry path case-insensitively
+        #[arg(short = 'i', long)]
+        ignore_case: bool,
+        /// Write the data as stored, without decompressing it
+        #[arg(long)]
+        raw: bool
+    }
+}
+
+// Global options for opening archives
+struct OpenOpts {
+    profile: Option<String>,
+    strict: bool,
+    raw_data: bool
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum OnConflict {
+    Overwrite,
+    Skip,
+    Fail
+}
+
+impl From<OnConflict> for VtPackOverwritePolicy {
+    fn from(on_conflict: OnConflict) -> Self {
+        match on_conflict {
+            OnConflict::Overwrite => Self::Overwrite,
+            OnConflict::Skip => Self::SkipExisting,
+            OnConflict::Fail => Self::FailOnConflict
+        }
+    }
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum TimestampField {
+    Unk2,
+    Unk3Unk4
+}
+
+impl From<TimestampField> for VtPackTimestampField {
+    fn from(field: TimestampField) -> Self {
+        match field {
+            TimestampField::Unk2 => Self::Unk2,
+            TimestampField::Unk3Unk4 => Self::Unk3Unk4
+        }
+    }
+}
+
+#[derive(Args)]
+struct FilterArgs {
+    /// Only extract entries matching this glob (e.g. "textures/**/*.dds")
+    #[arg(long = "include", value_name = "GLOB")]
+    include_globs: Vec<String>,
+    /// Skip entries matching this glob
+    #[arg(long = "exclude", value_name = "GLOB")]
+    exclude_globs: Vec<String>,
+    /// Only extract entries matching this regex
+    #[arg(long = "include-regex", value_name = "REGEX")]
+    include_regexes: Vec<String>,
+    /// Skip entries matching this regex
+    #[arg(long = "exclude-regex", value_name = "REGEX")]
+    exclude_regexes: Vec<String>,
+    /// Only extract files (their parent directories are still created)
+    #[arg(long)]
+    files_only: bool,
+    /// Skip files smaller than this many bytes
+    #[arg(long)]
+    min_size: Option<u64>,
+    /// Skip files larger than this many bytes
+    #[arg(long)]
+    max_size: Option<u64>,
+    /// Match paths and patterns case-sensitively
+    #[arg(long)]
+    case_sensitive: bool
+}
+
+impl FilterArgs {
+    fn make_filter(&self, paths: &[String]) -> Result<VtPackEntryFilter> {
+        let ignore_case = !self.case_sensitive;
+        let mut filter = VtPackEntryFilter::new().ignore_case(ignore_case);
+
+        for path in paths {
+            filter = filter.include(VtPackPattern::prefix(path, ignore_case)?);
+        }
+        for glob in self.include_globs.iter() {
+            filter = filter.include_glob(glob)?;
+        }
+        for glob in self.exclude_globs.iter() {
+            filter = filter.exclude_glob(glob)?;
+        }
+        for regex in self.include_regexes.iter() {
+            filter = filter.include_regex(regex)?;
+        }
+        for regex in self.exclude_regexes.iter() {
+            filter = filter.exclude_regex(regex)?;
+        }
+
+        if self.files_only {
+            filter = filter.kind(VtPackEntryKind::File);
+        }
+        if let Some(min_size) = self.min_size {
+            filter = filter.min_size(min_size);
+        }
+        if let Some(max_size) = self.max_size {
+            filter = filter.max_size(max_size);
+        }
+
+        Ok(filter)
+    }
+}
+
+fn open_archive(archive: &Path, opts: &OpenOpts) -> Result<VtPackArchive<BufReader<File>>> {
+    let registry = VtPackProfileRegistry::new();
+    let f = File::open(archive).map_err(|err| VtPackError::PathIo(archive.to_path_buf(), err))?;
+    let mut reader = BufReader::new(f);
+
+    let profile = match opts.profile.as_deref() {
+        Some(name) => Some(registry.get(name).ok_or_else(|| VtPackError::UnknownProfile(name.to_string()))?),
+        None => registry.detect_from_reader(&mut reader)?
+    };
+    let mut vtpack = VtPackArchive::new_with_options(reader, &VtPackReadOptions::new().strict(opts.strict).raw_data(opts.raw_data))?;
+    if let Some(profile) = profile {
+        vtpack.apply_profile(profile)?;
+    }
+    Ok(vtpack)
+}
+
+// Non-seekable input can't be sniffed beforehand, so the profile is detected from the parsed header
+fn open_stream_archive<R: Read>(reader: R, opts: &OpenOpts) -> Result<VtPackStreamArchive<R>> {
+    let registry = VtPackProfileRegistry::new();
+    let mut vtpack = VtPackStreamArchive::new_with_options(reader, &VtPackReadOptions::new().strict(opts.strict).raw_data(opts.raw_data))?;
+
+    let profile = match opts.profile.as_deref() {
+        Some(name) => Some(registry.get(name).ok_or_else(|| VtPackError::UnknownProfile(name.to_string()))?),
+        None => registry.detect(&vtpack.get_file().get_raw().header, vtpack.get_file().get_endian())
+    };
+    if let Some(profile) = profile {
+        vtpack.get_file_mut().apply_profile(profile)?;
+    }
+    Ok(vtpack)
+}
+
+fn list(archive: PathBuf, json: bool, opts: &OpenOpts) -> Result<()> {
+    let vtpack = open_archive(&archive, opts)?;
+    let mut out = io::stdout().lock();
+
+    if json {
+        let entries: Vec<_> = vtpack.entries().iter().map(|entry| serde_json::json!({
+            "path": entry.get_path(),
+            "is_file": entry.is_file(),
+            "size": entry.get_file_size(),
+            "offset": entry.get_file_data_abs_offset()
+        })).collect();
+        writeln!(out, "{}", serde_json::to_string_pretty(&entries).unwrap())?;
+    }
+    else {
+        for entry in vtpack.entries() {
+            if entry.is_file() {
+                writeln!(out, "{:>12} {:#012X} {}", entry.get_file_size(), entry.get_file_data_abs_offset(), entry.get_path())?;
+            }
+            else {
+                writeln!(out, "{:>12} {:>12} {}{}", "<dir>", "", entry.get_path(), std::path::MAIN_SEPARATOR)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn info(archive: PathBuf, opts: &OpenOpts) -> Result<()> {
+    let vtpack = open_archive(&archive, opts)?;
+    let file = vtpack.get_file();
+    let raw = file.get_raw();
+    let header = &raw.header;
+
+    let file_count = file.list_entries().iter().filter(|entry| entry.is_file()).count();
+    let dir_count = file.list_entries().len() - file_count;
+    let total_size: u64 = file.list_entries().iter().filter(|entry| entry.is_file()).map(|entry| entry.get_file_size() as u64).sum();
+
+    println!("Archive:             {}", archive.display());
+    println!("Profile:             {}", vtpack.get_profile_name().map_or("(none)", |name| name.as_str()));
+    println!("Version:             {}", header.version);
+    println!("Byte order:          {:?}", file.get_endian());
+    println!("Header unk1:         {:#010X}", header.unk1);
+    println!("Header unk2:         {:#010X}", header.unk2);
+    match header.version.get_layout() {
+        VtPackVersion::Ver1 => {
+            println!("Header unk3:         {:#010X}", header.unk3_v1);
+            println!("Header unk4:         {:#010X}", header.unk4_v1);
+        }
+        _ => {
+            println!("Header unk3:         {:#018X}", header.unk3_v2);
+            println!("Header unk4:         {:#018X}", header.unk4_v2);
+        }
+    }
+    println!("String table offset: {:#X}", header.get_str_table_abs_offset());
+    println!("String table size:   {:#X}", raw.str_table.table_size);
+    println!("Entry count:         {}", header.entry_count);
+    println!("Files:               {}", file_count);
+    println!("Directories:         {}", dir_count);
+    println!("Total file size:     {}", total_size);
+
+    Ok(())
+}
+
+fn extract(archive: PathBuf, output: PathBuf, filter: VtPackEntryFilter, options: VtPackExtractOptions, timestamps: Option<TimestampField>, ordered: bool, opts: &OpenOpts) -> Result<bool> {
+    let timestamp_decoder = timestamps.map(|field| VtPackTimestampDecoder::new(field.into()));
+    let (report, failed) = if archive.as_os_str() == "-" {
+        let mut vtpack = open_stream_archive(io::stdin().lock(), opts)?;
+        if timestamp_decoder.is_some() {
+            vtpack.get_file_mut().set_timestamp_decoder(timestamp_decoder);
+        }
+        (vtpack.extract_with_options(&output, &filter, &options)?, Vec::new())
+    }
+    else {
+        let mut vtpack = open_archive(&archive, opts)?;
+        if timestamp_decoder.is_some() {
+            vtpack.set_timestamp_decoder(timestamp_decoder);
+        }
+        match options.worker_count {
+            1 if ordered => (vtpack.extract_ordered(&output, &filter, &options)?, Vec::new()),
+            1 => (vtpack.extract_with_options(&output, &filter, &options)?, Vec::new()),
+            _ => {
+                let parallel_report = vtpack.get_file().export_parallel_from_path(&archive, &output, &filter, &options)?;
+                let failed = parallel_report.get_failed().iter().map(|failed_entry| (vtpack.entries()[failed_entry.get_entry_index()].get_path().clone(), failed_entry.get_error().to_string())).collect();
+                (parallel_report.get_report().clone(), failed)
+            }
+        }
+    };
+
+    if report.cleaned_target() {
+        println!("{:<9} {}", "clean", output.display());
+    }
+    for extracted_entry in report.get_entries() {
+        let action = match extracted_entry.get_action() {
+            VtPackExtractAction::CreateDir => "mkdir",
+            VtPackExtractAction::Write => "write",
+            VtPackExtractAction::Overwrite => "overwrite",
+            VtPackExtractAction::Skip => "skip"
+        };
+        println!("{:<9} {}", action, extracted_entry.get_out_path().display());
+    }
+    for (path, err) in failed.iter() {
+        eprintln!("{:<9} {}: {}", "failed", path, err);
+    }
+    if report.is_dry_run() {
+        println!("(dry run, nothing was written)");
+    }
+
+    Ok(failed.is_empty())
+}
+
+fn verify(archive: PathBuf) -> Result<bool> {
+    let f = File::open(&archive).map_err(|err| VtPackError::PathIo(archive.clone(), err))?;
+    let report = VtPackFile::validate(&mut BufReader::new(f))?;
+
+    for problem in report.get_problems() {
+        println!("{}", problem);
+    }
+    println!("{}: {} entries checked, {} problems found", archive.display(), report.get_entry_count(), report.get_problems().len());
+
+    Ok(report.is_valid())
+}
+
+fn analyze(archives: Vec<PathBuf>, json: bool) -> Result<()> {
+    let mut analyzer = VtPackUnknownFieldAnalyzer::new();
+    for archive in archives.iter() {
+        let vtpack = VtPackArchive::open_path(archive)?;
+        analyzer.add_file(vtpack.get_file());
+    }
+    let report = analyzer.make_report();
+
+    if json {
+        let fields: Vec<_> = report.iter().map(|field| serde_json::json!({
+            "name": field.name,
+            "bits": field.bits,
+            "sample_count": field.sample_count,
+            "min": field.min,
+            "max": field.max,
+            "distinct_count": field.distinct_count,
+            "most_common": field.most_common,
+            "used_bits": field.used_bits,
+            "correlations": field.correlations.iter().map(|correlation| serde_json::json!({
+                "with": correlation.with,
+                "pearson": correlation.pearson,
+                "equal_fraction": correlation.equal_fraction
+            })).collect::<Vec<_>>(),
+            "filetime_fraction": field.filetime_fraction,
+            "looks_like_filetime": field.looks_like_filetime,
+            "looks_like_crc": field.looks_like_crc,
+            "looks_like_flags": field.looks_like_flags
+        })).collect();
+        println!("{}", serde_json::to_string_pretty(&fields).unwrap());
+        return Ok(());
+    }
+
+    println!("{} archives analyzed", analyzer.get_archive_count());
+    for field in report.iter() {
+        let width = field.bits as usize / 4 + 2;
+        println!();
+        println!("{} ({}-bit, {} samples)", field.name, field.bits, field.sample_count);
+        println!("  range:       {:#0w$X} - {:#0w$X}", field.min, field.max, w = width);
+        println!("  distinct:    {}", field.distinct_count);
+        println!("  used bits:   {:#0w$X}", field.used_bits, w = width);
+        let most_common: Vec<String> = field.most_common.iter().map(|(value, count)| format!("{:#X} (x{})", value, count)).collect();
+        println!("  most common: {}", most_common.join(", "));
+        for correlation in field.correlations.iter() {
+            let pearson = correlation.pearson.map_or("-".to_string(), |pearson| format!("{:+.3}", pearson));
+            println!("  vs {:<21} pearson {:>6}, equal {:.1}%", correlation.with, pearson, correlation.equal_fraction * 100.0);
+        }
+
+        let mut guesses = Vec::new();
+        if field.looks_like_filetime {
+            guesses.push("FILETIME");
+        }
+        if field.looks_like_crc {
+            guesses.push("CRC/hash");
+        }
+        if field.looks_like_flags {
+            guesses.push("flag bitset");
+        }
+        if !guesses.is_empty() {
+            println!("  looks like:  {}", guesses.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+fn checksums(archives: Vec<PathBuf>, paths_only: bool, all: bool, opts: &OpenOpts) -> Result<()> {
+    let mut tester = VtPackChecksumTester::new().hash_data(!paths_only);
+    for archive in archives.iter() {
+        let mut vtpack = open_archive(archive, opts)?;
+        let (file, reader) = vtpack.get_file_and_reader();
+        tester.add_file(file, reader)?;
+    }
+
+    let report = tester.make_report();
+    let mut shown_count = 0;
+    for hypothesis in report.iter().filter(|hypothesis| all || hypothesis.is_consistent()).filter(|hypothesis| hypothesis.get_match_count() > 0) {
+        println!("{:<48} files {:>6}/{:<6} dirs {:>6}/{:<6}{}", hypothesis.check.to_string(), hypothesis.file_matches, hypothesis.file_samples, hypothesis.dir_matches, hypothesis.dir_samples, if hypothesis.is_consistent() { " (consistent)" } else { "" });
+        shown_count += 1;
+    }
+    if shown_count == 0 {
+        println!("No checksum matches any unknown field consistently");
+    }
+
+    Ok(())
+}
+
+fn repack(archive: PathBuf, output: PathBuf) -> Result<bool> {
+    VtPackFile::repack_file(&archive, &output)?;
+
+    let open = |path: &PathBuf| File::open(path).map(BufReader::new).map_err(|err| VtPackError::PathIo(path.clone(), err));
+    let (mut in_bytes, mut out_bytes) = (open(&archive)?.bytes(), open(&output)?.bytes());
+    let mut offset: u64 = 0;
+    loop {
+        match (in_bytes.next().transpose()?, out_bytes.next().transpose()?) {
+            (None, None) => break,
+            (in_byte, out_byte) if in_byte == out_byte => offset += 1,
+            _ => {
+                println!("{}: output differs from the original at offset {:#X}", output.display(), offset);
+                return Ok(false);
+            }
+        }
+    }
+
+    println!("{}: identical to the original ({} bytes)", output.display(), offset);
+    Ok(true)
+}
+
+fn cat(archive: PathBuf, path: String, ignore_case: bool, raw: bool, opts: &OpenOpts) -> Result<()> {
+    let mut vtpack = open_archive(&archive, opts)?;
+    // The profile might already ignore case
+    if ignore_case {
+        vtpack.set_ignore_case(true);
+    }
+
+    let mut out = io::stdout().lock();
+    if raw {
+        io::copy(&mut vtpack.open_raw(&path)?, &mut out)?;
+    }
+    else {
+        io::copy(&mut vtpack.open(&path)?, &mut out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let opts = OpenOpts {
+        profile: cli.profile,
+        strict: cli.strict,
+        raw_data: false
+    };
+    let res = match cli.command {
+        Command::List { archive, json } => list(archive, json, &opts),
+        Command::Info { archive } => info(archive, &opts),
+        Command::Extract { archive, output, paths, filter, clean, on_conflict, dry_run, timestamps, jobs, ordered, raw } => {
+            let opts = OpenOpts {
+                raw_data: raw,
+                ..opts
+            };
+            let options = VtPackExtractOptions::new().clean_target(clean).overwrite_policy(on_conflict.into()).dry_run(dry_run).worker_count(jobs.unwrap_or(1)).protect_path(&archive);
+            let output = output.unwrap_or_else(|| PathBuf::from("."));
+            match filter.make_filter(&paths).and_then(|filter| extract(archive, output, filter, options, timestamps, ordered, &opts)) {
+                Ok(true) => Ok(()),
+                Ok(false) => return ExitCode::FAILURE,
+                Err(err) => Err(err)
+            }
+        }
+        Command::Verify { archive } => match verify(archive) {
+            Ok(true) => Ok(()),
+            Ok(false) => return ExitCode::FAILURE,
+            Err(err) => Err(err)
+        },
+        Command::Analyze { archives, json } => analyze(archives, json),
+        Command::Checksums { archives, paths_only, all } => checksums(archives, paths_only, all, &opts),
+        Command::Repack { archive, output } => match repack(archive, output) {
+            Ok(true) => Ok(()),
+            Ok(false) => return ExitCode::FAILURE,
+            Err(err) => Err(err)
+        },
+        Command::Cat { archive, path, ignore_case, raw } => cat(archive, path, ignore_case, raw, &opts)
+    };
+
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("vtpack: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use std::{collections::{BTreeMap, HashMap}, fs::File, io::{self, Read, Seek, Write}, path::{Path, PathBuf}, time::SystemTime};
+use binrw::{BinWrite, Endian};
+use crate::{VtPackVersion, VtPackRawHeader, VtPackStringTable, VtPackRawEntryHeader, VtPackTimestampField, RAW_ENTRY_HEADER_SIZE, get_raw_header_size, system_time_to_filetime, VtPackError, Result, IoResultExt, path::split_path_components};
+
+pub enum VtPackDataSource {
+    Path(PathBuf),
+    Bytes(Vec<u8>)
+}
+
+impl VtPackDataSource {
+    fn get_size(&self) -> Result<u64> {
+        match self {
+            Self::Path(path) => Ok(std::fs::metadata(path).with_path(path)?.len()),
+            Self::Bytes(data) => Ok(data.len() as u64)
+        }
+    }
+
+    // In-memory data has no timestamp
+    fn get_modified_time(&self) -> Result<Option<SystemTime>> {
+        match self {
+            Self::Path(path) => Ok(Some(std::fs::metadata(path).and_then(|metadata| metadata.modified()).with_path(path)?)),
+            Self::Bytes(_) => Ok(None)
+        }
+    }
+
+    fn open(&self) -> Result<Box<dyn Read + '_>> {
+        match self {
+            Self::Path(path) => Ok(Box::new(File::open(path).with_path(path)?)),
+            Self::Bytes(data) => Ok(Box::new(data.as_slice()))
+        }
+    }
+}
+
+pub struct VtPackBuilder {
+    version: VtPackVersion,
+    data_alignment: u64,
+    timestamp_field: Option<VtPackTimestampField>,
+    path_separator: char,
+    endian: Endian,
+    // Path components -> data source (None for directories), sorted so that parents always come before their children
+    entries: BTreeMap<Vec<String>, Option<VtPackDataSource>>
+}
+
+fn split_path(path: &str) -> Vec<String> {
+    split_path_components(path).map(String::from).collect()
+}
+
+fn align_up(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+struct StringTableBuilder {
+    data: Vec<u8>,
+    offsets: HashMap<String, u32>
+}
+
+impl StringTableBuilder {
+    fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            offsets: HashMap::new()
+        }
+    }
+
+    fn add(&mut self, s: &str) -> u32 {
+        if let Some(offset) = self.offsets.get(s) {
+            return *offset;
+        }
+
+        let offset = self.data.len() as u32;
+        self.data.extend_from_slice(s.as_bytes());
+        self.data.push(0);
+        self.offsets.insert(s.to_string(), offset);
+        offset
+    }
+}
+
+impl VtPackBuilder {
+    pub fn new(version: VtPackVersion) -> Self {
+        Self {
+            version,
+            data_alignment: 1,
+            timestamp_field: None,
+            path_separator: '\\',
+            endian: Endian::Little,
+            entries: BTreeMap::new()
+        }
+    }
+
+    pub fn from_entries<S: AsRef<str>, I: IntoIterator<Item = (S, VtPackDataSource)>>(version: VtPackVersion, entries: I) -> Result<Self> {
+        let mut builder = Self::new(version);
+        for (path, source) in entries {
+            builder.add_file(path, source)?;
+        }
+        Ok(builder)
+    }
+
+    pub fn from_dir<P: AsRef<Path>>(version: VtPackVersion, dir: P) -> Result<Self> {
+        let mut builder = Self::new(version);
+        builder.add_dir_tree(dir, "")?;
+        Ok(builder)
+    }
+
+    pub fn set_data_alignment(&mut self, alignment: u64) {
+        self.data_alignment = alignment.max(1);
+    }
+
+    // Source file mtimes are stored as FILETIMEs in this field, otherwise it's left zeroed like every other unknown
+    pub fn set_timestamp_field(&mut self, field: Option<VtPackTimestampField>) {
+        self.timestamp_field = field;
+    }
+
+    // Used in the stored directory strings
+    pub fn set_path_separator(&mut self, path_separator: char) {
+        self.path_separator = path_separator;
+    }
+
+    pub fn set_endian(&mut self, endian: Endian) {
+        self.endian = endian;
+    }
+
+    // Checks the whole path before anything is added: a file can't also be a directory, nor be inside another file
+    fn check_new_path(&self, path: &str, comps: &[String], is_file: bool) -> Result<()> {
+        let invalid_path = |reason| Err(VtPackError::InvalidEntryPath {
+            path: path.to_string(),
+            reason
+        });
+
+        if comps.is_empty() {
+            return invalid_path("has no path components");
+        }
+        for i in 1..comps.len() {
+            if let Some(Some(_)) = self.entries.get(&comps[..i]) {
+                return invalid_path("a parent directory was already added as a file");
+            }
+        }
+        match self.entries.get(comps) {
+            Some(Some(_)) if !is_file => invalid_path("was already added as a file"),
+            Some(None) if is_file => invalid_path("was already added as a directory"),
+            _ => Ok(())
+        }
+    }
+
+    pub fn add_dir<S: AsRef<str>>(&mut self, path: S) -> Result<()> {
+        let comps = split_path(path.as_ref());
+        self.check_new_path(path.as_ref(), &comps, false)?;
+
+        for i in 1..=comps.len() {
+            self.entries.entry(comps[..i].to_vec()).or_insert(None);
+        }
+        Ok(())
+    }
+
+    // Adding the same file path again replaces its data
+    pub fn add_file<S: AsRef<str>>(&mut self, path: S, source: VtPackDataSource) -> Result<()> {
+        let comps = split_path(path.as_ref());
+        self.check_new_path(path.as_ref(), &comps, true)?;
+
+        for i in 1..comps.len() {
+            self.entries.entry(comps[..i].to_vec()).or_insert(None);
+        }
+        self.entries.insert(comps, Some(source));
+        Ok(())
+    }
+
+    pub fn add_dir_tree<P: AsRef<Path>, S: AsRef<str>>(&mut self, dir: P, base_path: S) -> Result<()> {
+        let dir = dir.as_ref();
+        let mut dir_entries = std::fs::read_dir(dir).and_then(|entries| entries.collect::<io::Result<Vec<_>>>()).with_path(dir)?;
+        dir_entries.sort_by_key(|entry| entry.file_name());
+
+        for dir_entry in dir_entries {
+            let name = dir_entry.file_name().to_string_lossy().to_string();
+            let path = format!("{}\\{}", base_path.as_ref(), name);
+
+            if dir_entry.file_type().with_path(dir_entry.path())?.is_dir() {
+                self.add_dir(&path)?;
+                self.add_dir_tree(dir_entry.path(), &path)?;
+            }
+            else {
+                self.add_file(&path, VtPackDataSource::Path(dir_entry.path()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Layout: header, string table, entry headers, file data
+    pub fn write<W: Write + Seek>(&self, writer: &mut W) -> Result<()> {
+        let mut str_table = StringTableBuilder::new();
+        let mut raw_entries: Vec<VtPackRawEntryHeader> = Vec::with_capacity(self.entries.len());
+        let mut sources: Vec<(&VtPackDataSource, u64)> = Vec::new();
+
+        for (comps, source) in self.entries.iter() {
+            let (name, dir_comps) = comps.split_last().unwrap();
+            let separator = self.path_separator.to_string();
+            let dir_str = format!("{}{}", separator, dir_comps.join(&separator));
+
+            let file_size = match source {
+                Some(source) => {
+                    let size = source.get_size()?;
+                    sources.push((source, size));
+                    size
+                }
+                None => 0
+            };
+
+            let mut raw_entry = VtPackRawEntryHeader {
+                path_name_str_table_offset: str_table.add(name),
+                path_dir_str_table_offset: str_table.add(&dir_str),
+                unk1: 0,
+                file_size,
+                unk2: 0,
+                file_data_abs_offset: 0,
+                unk3: 0,
+                unk4: 0
+            };
+            if let (Some(field), Some(source)) = (self.timestamp_field, source) {
+                if let Some(filetime) = source.get_modified_time()?.and_then(system_time_to_filetime) {
+                    field.set_value(&mut raw_entry, filetime);
+                }
+            }
+            raw_entries.push(raw_entry);
+        }
+
+        let str_table_abs_offset = get_raw_header_size(self.version);
+        let str_table = VtPackStringTable {
+            table_size: str_table.data.len() as u32,
+            table_data: str_table.data
+        };
+        let entries_abs_offset = str_table_abs_offset + 4 + str_table.table_data.len() as u64;
+        let mut cur_data_offset = entries_abs_offset + raw_entries.len() as u64 * RAW_ENTRY_HEADER_SIZE;
+
+        for (raw_entry, source) in raw_entries.iter_mut().zip(self.entries.values()) {
+            if source.is_some() {
+                cur_data_offset = align_up(cur_data_offset, self.data_alignment);
+                raw_entry.file_data_abs_offset = cur_data_offset;
+                cur_data_offset += raw_entry.file_size;
+            }
+        }
+
+        let base_pos = writer.stream_position()?;
+        VtPackRawHeader::new(self.version, raw_entries.len() as u32, str_table_abs_offset).write_options(writer, self.endian, ())?;
+        str_table.write_options(writer, self.endian, ())?;
+        raw_entries.write_options(writer, self.endian, ())?;
+
+        let mut source_iter = sources.into_iter();
+        for raw_entry in raw_entries.iter().filter(|entry| entry.file_data_abs_offset != 0) {
+            let (source, size) = source_iter.next().unwrap();
+
+            let cur_pos = writer.stream_position()? - base_pos;
+            if cur_pos < raw_entry.file_data_abs_offset {
+                io::copy(&mut io::repeat(0).take(raw_entry.file_data_abs_offset - cur_pos), writer)?;
+            }
+
+            let copied_size = io::copy(&mut source.open()?.take(size), writer)?;
+            if copied_size != size {
+                return Err(VtPackError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "file data source changed size while building")));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut writer = io::BufWriter::new(File::create(path).with_path(path)?);
+        self.write(&mut writer)?;
+        writer.flush().with_path(path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::*;
+    use crate::VtPackFile;
+
+    fn build_test_archive(version: VtPackVersion, endian: Endian, data_alignment: u64) -> (Vec<u8>, Vec<(&'static str, Vec<u8>)>) {
+        let files = vec![
+            ("root.txt", b"root file".to_vec()),
+            ("empty.bin", Vec::new()),
+            ("data\\a.bin", vec![0xA5; 0x33]),
+            ("data/sub\\b.bin", (0..=255).collect()),
+            ("data\\sub\\empty.bin", Vec::new())
+        ];
+
+        let mut builder = VtPackBuilder::new(version);
+        builder.set_endian(endian);
+        builder.set_data_alignment(data_alignment);
+        for (path, data) in files.iter() {
+            builder.add_file(path, VtPackDataSource::Bytes(data.clone())).unwrap();
+        }
+        builder.add_dir("data\\empty_dir").unwrap();
+        builder.add_dir("other_empty_dir").unwrap();
+
+        let mut archive = Cursor::new(Vec::new());
+        builder.write(&mut archive).unwrap();
+        (archive.into_inner(), files)
+    }
+
+    #[test]
+    fn build_and_read_back() {
+        for version in [VtPackVersion::Ver1, VtPackVersion::Ver2] {
+            for endian in [Endian::Little, Endian::Big] {
+                for data_alignment in [1, 0x10, 0x800] {
+                    let (archive, files) = build_test_archive(version, endian, data_alignment);
+                    let mut reader = Cursor::new(&archive);
+                    let file = VtPackFile::new(&mut reader).unwrap();
+                    assert_eq!(file.get_raw().header.version, version);
+                    assert_eq!(file.get_endian(), endian);
+
+                    // The files plus data, data/sub and the two empty directories
+                    assert_eq!(file.list_entries().len(), files.len() + 4);
+                    for (path, data) in files.iter() {
+                        let entry = file.find(path).unwrap_or_else(|| panic!("'{}' is missing", path));
+                        assert!(entry.is_file());
+                        assert_eq!(entry.get_file_data_abs_offset() % data_alignment, 0);
+
+                        let mut entry_data = Vec::new();
+                        file.open_entry(&mut reader, entry).unwrap().read_to_end(&mut entry_data).unwrap();
+                        assert_eq!(&entry_data, data, "'{}' in v{} {:?}", path, version, endian);
+                    }
+                    for path in ["data", "data/sub", "data/empty_dir", "other_empty_dir"] {
+                        assert!(file.find(path).unwrap_or_else(|| panic!("'{}' is missing", path)).is_dir());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reject_path_collisions() {
+        let mut builder = VtPackBuilder::new(VtPackVersion::Ver2);
+        builder.add_dir("data\\dir").unwrap();
+        assert!(matches!(builder.add_file("data/dir", VtPackDataSource::Bytes(Vec::new())), Err(VtPackError::InvalidEntryPath { .. })));
+        assert!(matches!(builder.add_file("data", VtPackDataSource::Bytes(Vec::new())), Err(VtPackError::InvalidEntryPath { .. })));
+
+        builder.add_file("data\\file.bin", VtPackDataSource::Bytes(Vec::new())).unwrap();
+        assert!(matches!(builder.add_dir("data/file.bin"), Err(VtPackError::InvalidEntryPath { .. })));
+        assert!(matches!(builder.add_dir("data/file.bin/dir"), Err(VtPackError::InvalidEntryPath { .. })));
+        assert!(matches!(builder.add_file("data\\file.bin\\inner.bin", VtPackDataSource::Bytes(Vec::new())), Err(VtPackError::InvalidEntryPath { .. })));
+
+        // Nothing from the rejected paths was added
+        assert_eq!(builder.entries.len(), 3);
+        builder.add_file("data\\file.bin", VtPackDataSource::Bytes(vec![1])).unwrap();
+        builder.add_dir("data/dir").unwrap();
+    }
+
+    #[test]
+    fn reject_empty_paths() {
+        let mut builder = VtPackBuilder::new(VtPackVersion::Ver2);
+        for path in ["", "\\", "/\\/"] {
+            assert!(matches!(builder.add_file(path, VtPackDataSource::Bytes(vec![1])), Err(VtPackError::InvalidEntryPath { .. })));
+            assert!(matches!(builder.add_dir(path), Err(VtPackError::InvalidEntryPath { .. })));
+        }
+        assert!(builder.entries.is_empty());
+    }
+}
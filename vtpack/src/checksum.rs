@@ -0,0 +1,555 @@
+use std::{collections::BTreeMap, fmt, io::{self, Read, Seek}};
+use crate::{VtPackFile, VtPackProcessedEntry, VtPackRawEntryHeader, VtPackError, Result, COPY_CHUNK_SIZE};
+
+const fn make_crc32_table(poly: u32, reflected: bool) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = if reflected { i as u32 } else { (i as u32) << 24 };
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if reflected {
+                if crc & 1 != 0 { (crc >> 1) ^ poly } else { crc >> 1 }
+            }
+            else if crc & 0x80000000 != 0 {
+                (crc << 1) ^ poly
+            }
+            else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+// Reflected polynomials are bit-reversed
+static CRC32_TABLE: [u32; 256] = make_crc32_table(0xEDB88320, true);
+static CRC32_MSB_TABLE: [u32; 256] = make_crc32_table(0x04C11DB7, false);
+static CRC32C_TABLE: [u32; 256] = make_crc32_table(0x82F63B78, true);
+
+const ADLER32_MOD: u32 = 65521;
+const FNV32_OFFSET_BASIS: u32 = 0x811C9DC5;
+const FNV32_PRIME: u32 = 0x01000193;
+const FNV64_OFFSET_BASIS: u64 = 0xCBF29CE484222325;
+const FNV64_PRIME: u64 = 0x00000100000001B3;
+const DJB2_INIT: u32 = 5381;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum VtPackChecksumAlgorithm {
+    // CRC-32/ISO-HDLC, as used by zlib/zip
+    Crc32,
+    Crc32Bzip2,
+    Crc32Jamcrc,
+    Crc32Mpeg2,
+    Crc32c,
+    Adler32,
+    Fnv1_32,
+    Fnv1a32,
+    Fnv1a64,
+    Djb2,
+    Djb2Xor
+}
+
+impl VtPackChecksumAlgorithm {
+    pub const ALL: [Self; 11] = [Self::Crc32, Self::Crc32Bzip2, Self::Crc32Jamcrc, Self::Crc32Mpeg2, Self::Crc32c, Self::Adler32, Self::Fnv1_32, Self::Fnv1a32, Self::Fnv1a64, Self::Djb2, Self::Djb2Xor];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Crc32 => "crc32",
+            Self::Crc32Bzip2 => "crc32-bzip2",
+            Self::Crc32Jamcrc => "crc32-jamcrc",
+            Self::Crc32Mpeg2 => "crc32-mpeg2",
+            Self::Crc32c => "crc32c",
+            Self::Adler32 => "adler32",
+            Self::Fnv1_32 => "fnv1-32",
+            Self::Fnv1a32 => "fnv1a-32",
+            Self::Fnv1a64 => "fnv1a-64",
+            Self::Djb2 => "djb2",
+            Self::Djb2Xor => "djb2-xor"
+        }
+    }
+
+    pub fn get_bits(&self) -> u32 {
+        match self {
+            Self::Fnv1a64 => 64,
+            _ => 32
+        }
+    }
+
+    pub fn start(&self) -> VtPackChecksum {
+        let state = match self {
+            Self::Crc32 | Self::Crc32Jamcrc | Self::Crc32Bzip2 | Self::Crc32Mpeg2 | Self::Crc32c => u32::MAX as u64,
+            Self::Adler32 => 1,
+            Self::Fnv1_32 | Self::Fnv1a32 => FNV32_OFFSET_BASIS as u64,
+            Self::Fnv1a64 => FNV64_OFFSET_BASIS,
+            Self::Djb2 | Self::Djb2Xor => DJB2_INIT as u64
+        };
+        VtPackChecksum {
+            algorithm: *self,
+            state
+        }
+    }
+
+    pub fn compute(&self, data: &[u8]) -> u64 {
+        let mut checksum = self.start();
+        checksum.update(data);
+        checksum.finish()
+    }
+}
+
+impl fmt::Display for VtPackChecksumAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.get_name())
+    }
+}
+
+// Incremental checksum, so that entry data doesn't need to be fully loaded
+#[derive(Copy, Clone, Debug)]
+pub struct VtPackChecksum {
+    algorithm: VtPackChecksumAlgorithm,
+    state: u64
+}
+
+impl VtPackChecksum {
+    pub fn update(&mut self, data: &[u8]) {
+        match self.algorithm {
+            VtPackChecksumAlgorithm::Crc32 | VtPackChecksumAlgorithm::Crc32Jamcrc => self.state = update_crc32_reflected(&CRC32_TABLE, self.state as u32, data) as u64,
+            VtPackChecksumAlgorithm::Crc32c => self.state = update_crc32_reflected(&CRC32C_TABLE, self.state as u32, data) as u64,
+            VtPackChecksumAlgorithm::Crc32Bzip2 | VtPackChecksumAlgorithm::Crc32Mpeg2 => {
+                let mut crc = self.state as u32;
+                for byte in data {
+                    crc = (crc << 8) ^ CRC32_MSB_TABLE[((crc >> 24) as u8 ^ *byte) as usize];
+                }
+                self.state = crc as u64;
+            }
+            VtPackChecksumAlgorithm::Adler32 => {
+                let (mut a, mut b) = (self.state as u32 & 0xFFFF, (self.state >> 16) as u32);
+                // Reducing every 4K bytes is enough to never overflow
+                for chunk in data.chunks(0x1000) {
+                    for byte in chunk {
+                        a += *byte as u32;
+                        b += a;
+                    }
+                    a %= ADLER32_MOD;
+                    b %= ADLER32_MOD;
+                }
+                self.state = ((b << 16) | a) as u64;
+            }
+            VtPackChecksumAlgorithm::Fnv1_32 => {
+                let mut hash = self.state as u32;
+                for byte in data {
+                    hash = hash.wrapping_mul(FNV32_PRIME) ^ *byte as u32;
+                }
+                self.state = hash as u64;
+            }
+            VtPackChecksumAlgorithm::Fnv1a32 => {
+                let mut hash = self.state as u32;
+                for byte in data {
+                    hash = (hash ^ *byte as u32).wrapping_mul(FNV32_PRIME);
+                }
+                self.state = hash as u64;
+            }
+            VtPackChecksumAlgorithm::Fnv1a64 => {
+                for byte in data {
+                    self.state = (self.state ^ *byte as u64).wrapping_mul(FNV64_PRIME);
+                }
+            }
+            VtPackChecksumAlgorithm::Djb2 => {
+                let mut hash = self.state as u32;
+                for byte in data {
+                    hash = hash.wrapping_mul(33).wrapping_add(*byte as u32);
+                }
+                self.state = hash as u64;
+            }
+            VtPackChecksumAlgorithm::Djb2Xor => {
+                let mut hash = self.state as u32;
+                for byte in data {
+                    hash = hash.wrapping_mul(33) ^ *byte as u32;
+                }
+                self.state = hash as u64;
+            }
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        match self.algorithm {
+            VtPackChecksumAlgorithm::Crc32 | VtPackChecksumAlgorithm::Crc32Bzip2 | VtPackChecksumAlgorithm::Crc32c => !(self.state as u32) as u64,
+            _ => self.state
+        }
+    }
+}
+
+fn update_crc32_reflected(table: &[u32; 256], mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc = (crc >> 8) ^ table[(crc as u8 ^ *byte) as usize];
+    }
+    crc
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum VtPackPathForm {
+    // Exactly as stored, like "\dir\name"
+    Raw,
+    // Without the leading separator, like "dir\name"
+    Relative,
+    // Like "dir/name"
+    ForwardSlash,
+    // Only the name
+    Name
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum VtPackCaseFold {
+    Keep,
+    Lower,
+    Upper
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum VtPackChecksumInput {
+    Data,
+    Path(VtPackPathForm, VtPackCaseFold)
+}
+
+impl VtPackChecksumInput {
+    pub fn all() -> Vec<Self> {
+        let mut inputs = vec![Self::Data];
+        for form in [VtPackPathForm::Raw, VtPackPathForm::Relative, VtPackPathForm::ForwardSlash, VtPackPathForm::Name] {
+            for case_fold in [VtPackCaseFold::Keep, VtPackCaseFold::Lower, VtPackCaseFold::Upper] {
+                inputs.push(Self::Path(form, case_fold));
+            }
+        }
+        inputs
+    }
+
+    // None for inputs that don't apply to the entry (data of a directory)
+    pub fn get_path_bytes(&self, entry: &VtPackProcessedEntry) -> Option<Vec<u8>> {
+        let Self::Path(form, case_fold) = self else {
+            return None;
+        };
+
+        let raw_path = format!("{}\\{}", entry.get_raw_dir(), entry.get_raw_name()).replace("\\\\", "\\");
+        let path = match form {
+            VtPackPathForm::Raw => raw_path,
+            VtPackPathForm::Relative => raw_path.trim_start_matches('\\').to_string(),
+            VtPackPathForm::ForwardSlash => raw_path.trim_start_matches('\\').replace('\\', "/"),
+            VtPackPathForm::Name => entry.get_raw_name().clone()
+        };
+        let path = match case_fold {
+            VtPackCaseFold::Keep => path,
+            VtPackCaseFold::Lower => path.to_lowercase(),
+            VtPackCaseFold::Upper => path.to_uppercase()
+        };
+        Some(path.into_bytes())
+    }
+}
+
+impl fmt::Display for VtPackChecksumInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Data => write!(f, "data"),
+            Self::Path(form, case_fold) => {
+                let form = match form {
+                    VtPackPathForm::Raw => "raw-path",
+                    VtPackPathForm::Relative => "relative-path",
+                    VtPackPathForm::ForwardSlash => "slash-path",
+                    VtPackPathForm::Name => "name"
+                };
+                match case_fold {
+                    VtPackCaseFold::Keep => write!(f, "{}", form),
+                    VtPackCaseFold::Lower => write!(f, "{} (lowercase)", form),
+                    VtPackCaseFold::Upper => write!(f, "{} (uppercase)", form)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum VtPackEntryField {
+    Unk1,
+    Unk2,
+    Unk2Low,
+    Unk2High,
+    Unk3,
+    Unk4
+}
+
+impl VtPackEntryField {
+    pub const ALL: [Self; 6] = [Self::Unk1, Self::Unk2, Self::Unk2Low, Self::Unk2High, Self::Unk3, Self::Unk4];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Unk1 => "entry.unk1",
+            Self::Unk2 => "entry.unk2",
+            Self::Unk2Low => "entry.unk2 (low)",
+            Self::Unk2High => "entry.unk2 (high)",
+            Self::Unk3 => "entry.unk3",
+            Self::Unk4 => "entry.unk4"
+        }
+    }
+
+    pub fn get_bits(&self) -> u32 {
+        match self {
+            Self::Unk2 => 64,
+            _ => 32
+        }
+    }
+
+    pub fn get_value(&self, entry: &VtPackRawEntryHeader) -> u64 {
+        match self {
+            Self::Unk1 => entry.unk1 as u64,
+            Self::Unk2 => entry.unk2,
+            Self::Unk2Low => entry.unk2 & 0xFFFFFFFF,
+            Self::Unk2High => entry.unk2 >> 32,
+            Self::Unk3 => entry.unk3 as u64,
+            Self::Unk4 => entry.unk4 as u64
+        }
+    }
+}
+
+impl fmt::Display for VtPackEntryField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.get_name())
+    }
+}
+
+// An identified "field = checksum(input)" relation, usable to verify entries
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct VtPackIntegrityCheck {
+    pub algorithm: VtPackChecksumAlgorithm,
+    pub input: VtPackChecksumInput,
+    pub field: VtPackEntryField
+}
+
+impl VtPackIntegrityCheck {
+    pub fn new(algorithm: VtPackChecksumAlgorithm, input: VtPackChecksumInput, field: VtPackEntryField) -> Self {
+        Self {
+            algorithm,
+            input,
+            field
+        }
+    }
+
+    // Directories have no data, so data checks only apply to files
+    pub fn applies_to(&self, entry: &VtPackProcessedEntry) -> bool {
+        self.input != VtPackChecksumInput::Data || entry.is_file()
+    }
+
+    pub fn compute_path(&self, entry: &VtPackProcessedEntry) -> Option<u64> {
+        self.input.get_path_bytes(entry).map(|path| self.algorithm.compute(&path))
+    }
+}
+
+impl fmt::Display for VtPackIntegrityCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {}({})", self.field, self.algorithm, self.input)
+    }
+}
+
+// Feeds the whole entry data to every checksum at once
+fn checksum_entry_data<R: Seek + Read>(file: &VtPackFile, reader: &mut R, entry: &VtPackProcessedEntry, checksums: &mut [VtPackChecksum]) -> Result<()> {
+    let mut entry_reader = file.open_entry(reader, entry)?;
+    let mut chunk = vec![0; COPY_CHUNK_SIZE];
+    loop {
+        let read_len = match entry_reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read_len) => read_len,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into())
+        };
+        for checksum in checksums.iter_mut() {
+            checksum.update(&chunk[..read_len]);
+        }
+    }
+
+    if entry_reader.stream_position()? != entry_reader.get_size() {
+        return Err(VtPackError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, format!("entry '{}' data was cut short", entry.get_path()))));
+    }
+    Ok(())
+}
+
+impl VtPackFile {
+    pub fn verify_entry_integrity<R: Seek + Read>(&self, reader: &mut R, entry_idx: usize, check: &VtPackIntegrityCheck) -> Result<()> {
+        let entry = &self.p_entries[entry_idx];
+        if !check.applies_to(entry) {
+            return Ok(());
+        }
+
+        let actual = match check.compute_path(entry) {
+            Some(actual) => actual,
+            None => {
+                let mut checksum = [check.algorithm.start()];
+                checksum_entry_data(self, reader, entry, &mut checksum)?;
+                checksum[0].finish()
+            }
+        };
+
+        self.check_integrity_value(entry_idx, check, actual)
+    }
+
+    pub(crate) fn check_integrity_value(&self, entry_idx: usize, check: &VtPackIntegrityCheck, actual: u64) -> Result<()> {
+        let entry = &self.p_entries[entry_idx];
+        let expected = check.field.get_value(&self.raw.entries[entry_idx]);
+        if actual != expected {
+            return Err(VtPackError::IntegrityMismatch {
+                path: entry.get_path().clone(),
+                check: check.to_string(),
+                expected,
+                actual
+            });
+        }
+        Ok(())
+    }
+
+    // Returns the indices of the entries that failed the check
+    pub fn verify_integrity<R: Seek + Read>(&self, reader: &mut R, check: &VtPackIntegrityCheck) -> Result<Vec<usize>> {
+        let mut failed_entry_idxs = Vec::new();
+        for entry_idx in 0..self.p_entries.len() {
+            match self.verify_entry_integrity(reader, entry_idx, check) {
+                Ok(()) => {}
+                Err(VtPackError::IntegrityMismatch { .. }) => failed_entry_idxs.push(entry_idx),
+                Err(err) => return Err(err)
+            }
+        }
+        Ok(failed_entry_idxs)
+    }
+}
+
+// A hypothesis needs at least this many entries backing it before it's considered consistent
+const MIN_CONSISTENT_SAMPLE_COUNT: usize = 4;
+
+#[derive(Clone, Debug)]
+pub struct VtPackChecksumHypothesis {
+    pub check: VtPackIntegrityCheck,
+    pub file_matches: usize,
+    pub file_samples: usize,
+    pub dir_matches: usize,
+    pub dir_samples: usize
+}
+
+impl VtPackChecksumHypothesis {
+    pub fn get_match_count(&self) -> usize {
+        self.file_matches + self.dir_matches
+    }
+
+    pub fn get_sample_count(&self) -> usize {
+        self.file_samples + self.dir_samples
+    }
+
+    pub fn get_match_fraction(&self) -> f64 {
+        if self.get_sample_count() == 0 {
+            0.0
+        }
+        else {
+            self.get_match_count() as f64 / self.get_sample_count() as f64
+        }
+    }
+
+    // Directories might just leave the field empty, so only files need to match every time
+    pub fn is_consistent(&self) -> bool {
+        self.file_samples >= MIN_CONSISTENT_SAMPLE_COUNT && self.file_matches == self.file_samples
+    }
+}
+
+#[derive(Default)]
+struct MatchCounts {
+    file_matches: usize,
+    file_samples: usize,
+    dir_matches: usize,
+    dir_samples: usize
+}
+
+// Tests every checksum/input combination against every entry field of the same width, across any number of archives
+pub struct VtPackChecksumTester {
+    hash_data: bool,
+    archive_count: usize,
+    counts: BTreeMap<(VtPackChecksumAlgorithm, VtPackChecksumInput, VtPackEntryField), MatchCounts>
+}
+
+impl Default for VtPackChecksumTester {
+    fn default() -> Self {
+        Self {
+            hash_data: true,
+            archive_count: 0,
+            counts: BTreeMap::new()
+        }
+    }
+}
+
+impl VtPackChecksumTester {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Hashing entry data means reading the whole archive, path-only testing is much faster
+    pub fn hash_data(mut self, hash_data: bool) -> Self {
+        self.hash_data = hash_data;
+        self
+    }
+
+    fn push(&mut self, algorithm: VtPackChecksumAlgorithm, input: VtPackChecksumInput, raw_entry: &VtPackRawEntryHeader, is_file: bool, checksum: u64) {
+        for field in VtPackEntryField::ALL.iter().filter(|field| field.get_bits() == algorithm.get_bits()) {
+            let counts = self.counts.entry((algorithm, input, *field)).or_default();
+            let is_match = (field.get_value(raw_entry) == checksum) as usize;
+            if is_file {
+                counts.file_samples += 1;
+                counts.file_matches += is_match;
+            }
+            else {
+                counts.dir_samples += 1;
+                counts.dir_matches += is_match;
+            }
+        }
+    }
+
+    pub fn add_file<R: Seek + Read>(&mut self, file: &VtPackFile, reader: &mut R) -> Result<()> {
+        self.archive_count += 1;
+
+        for (entry_idx, entry) in file.list_entries().iter().enumerate() {
+            let raw_entry = &file.get_raw().entries[entry_idx];
+
+            for input in VtPackChecksumInput::all() {
+                if let Some(path) = input.get_path_bytes(entry) {
+                    for algorithm in VtPackChecksumAlgorithm::ALL {
+                        self.push(algorithm, input, raw_entry, entry.is_file(), algorithm.compute(&path));
+                    }
+                }
+            }
+
+            if self.hash_data && entry.is_file() {
+                let mut checksums = VtPackChecksumAlgorithm::ALL.map(|algorithm| algorithm.start());
+                checksum_entry_data(file, reader, entry, &mut checksums)?;
+                for checksum in checksums {
+                    self.push(checksum.algorithm, VtPackChecksumInput::Data, raw_entry, true, checksum.finish());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get_archive_count(&self) -> usize {
+        self.archive_count
+    }
+
+    // Sorted from best to worst match
+    pub fn make_report(&self) -> Vec<VtPackChecksumHypothesis> {
+        let mut hypotheses: Vec<_> = self.counts.iter().map(|((algorithm, input, field), counts)| VtPackChecksumHypothesis {
+            check: VtPackIntegrityCheck::new(*algorithm, *input, *field),
+            file_matches: counts.file_matches,
+            file_samples: counts.file_samples,
+            dir_matches: counts.dir_matches,
+            dir_samples: counts.dir_samples
+        }).collect();
+        hypotheses.sort_by(|a, b| b.is_consistent().cmp(&a.is_consistent()).then(b.get_match_fraction().total_cmp(&a.get_match_fraction())));
+        hypotheses
+    }
+
+    pub fn get_consistent_checks(&self) -> Vec<VtPackIntegrityCheck> {
+        self.make_report().into_iter().filter(|hypothesis| hypothesis.is_consistent()).map(|hypothesis| hypothesis.check).collect()
+    }
+}
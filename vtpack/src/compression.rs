@@ -0,0 +1,431 @@
+use std::{fmt, io::{self, Read, Seek, SeekFrom}};
+use flate2::{Decompress, FlushDecompress, Status, read::GzDecoder};
+use crate::{VtPackFile, VtPackProcessedEntry, VtPackRawEntryReader, VtPackEntryReader, VtPackError, Result};
+
+// Amount of entry data read to sniff compression headers
+pub(crate) const SNIFF_SIZE: u64 = 0x1000;
+
+// Deflate can't compress better than ~1032:1, anything beyond that can't be a size pair
+const MAX_COMPRESSION_RATIO: u64 = 1032;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum VtPackCompression {
+    Zlib,
+    // Raw deflate, without any header
+    Deflate,
+    Gzip,
+    Zstd,
+    Lz4,
+    Xz,
+    Lzma
+}
+
+impl VtPackCompression {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Zlib => "zlib",
+            Self::Deflate => "deflate",
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+            Self::Lz4 => "lz4",
+            Self::Xz => "xz",
+            Self::Lzma => "lzma"
+        }
+    }
+
+    // Other compressions can only be guessed from their header, and their entries are read as stored
+    pub fn is_supported(&self) -> bool {
+        matches!(self, Self::Zlib | Self::Deflate | Self::Gzip)
+    }
+
+    // Raw deflate has no header, so it's never sniffed here
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x1F, 0x8B, 0x08, ..] => Some(Self::Gzip),
+            [0x28, 0xB5, 0x2F, 0xFD, ..] => Some(Self::Zstd),
+            [0x04, 0x22, 0x4D, 0x18, ..] => Some(Self::Lz4),
+            [0xFD, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Self::Xz),
+            // Properties byte (lc=3, lp=0, pb=2 is what everything uses), a 2^n or 2^n+2^(n-1) dictionary size and the uncompressed size
+            [0x5D, d0, d1, d2, d3, _, _, _, _, _, _, _, _, ..] if is_lzma_dict_size(u32::from_le_bytes([*d0, *d1, *d2, *d3])) => Some(Self::Lzma),
+            // Deflate method, window size up to 32K and the header check
+            [cmf, flg, ..] if cmf & 0x0F == 8 && cmf >> 4 <= 7 && ((*cmf as u16) << 8 | *flg as u16).is_multiple_of(31) => Some(Self::Zlib),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for VtPackCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.get_name())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct VtPackCompressionInfo {
+    pub compression: VtPackCompression,
+    pub stored_size: u64,
+    pub uncompressed_size: u64
+}
+
+// Compressed entries are assumed to hold both sizes in file_size and unk2, in either order
+pub(crate) fn get_compressed_size_pair(file_size: u64, unk2: u64) -> Option<(u64, u64)> {
+    let (stored_size, uncompressed_size) = (file_size.min(unk2), file_size.max(unk2));
+    if stored_size == 0 || stored_size == uncompressed_size || uncompressed_size / stored_size > MAX_COMPRESSION_RATIO {
+        return None;
+    }
+    Some((stored_size, uncompressed_size))
+}
+
+fn is_lzma_dict_size(dict_size: u32) -> bool {
+    let high_bit = dict_size.checked_ilog2().unwrap_or(0);
+    dict_size >= 0x1000 && (dict_size.is_power_of_two() || dict_size == (1 << high_bit) | (1 << (high_bit - 1)))
+}
+
+// Only a quick filter: short data runs out before hitting an invalid block type or distance, so it proves nothing on its own
+fn looks_like_raw_deflate(data: &[u8]) -> bool {
+    let mut decompress = Decompress::new(false);
+    let mut out = vec![0; SNIFF_SIZE as usize * 4];
+    match decompress.decompress(data, &mut out, FlushDecompress::None) {
+        Ok(_) => decompress.total_out() > 0,
+        Err(_) => false
+    }
+}
+
+// Raw deflate has no header and zlib's is just two bytes, so they only count if all the data inflates to exactly the expected size
+fn inflates_to_size<R: Read + ?Sized>(reader: &mut R, zlib_header: bool, uncompressed_size: u64) -> io::Result<bool> {
+    let mut decompress = Decompress::new(zlib_header);
+    let mut in_chunk = vec![0; SNIFF_SIZE as usize];
+    let mut out_chunk = vec![0; SNIFF_SIZE as usize * 4];
+    loop {
+        let read_len = match reader.read(&mut in_chunk) {
+            Ok(read_len) => read_len,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err)
+        };
+        let flush = if read_len == 0 { FlushDecompress::Finish } else { FlushDecompress::None };
+
+        let mut input = &in_chunk[..read_len];
+        loop {
+            let (prev_total_in, prev_total_out) = (decompress.total_in(), decompress.total_out());
+            let status = match decompress.decompress(input, &mut out_chunk, flush) {
+                Ok(status) => status,
+                Err(_) => return Ok(false)
+            };
+            input = &input[(decompress.total_in() - prev_total_in) as usize..];
+
+            if decompress.total_out() > uncompressed_size {
+                return Ok(false);
+            }
+            if status == Status::StreamEnd {
+                return Ok(decompress.total_out() == uncompressed_size);
+            }
+            if decompress.total_in() == prev_total_in && decompress.total_out() == prev_total_out {
+                break;
+            }
+        }
+
+        // The data ended before the compressed stream did
+        if read_len == 0 {
+            return Ok(false);
+        }
+    }
+}
+
+// Stored .gz assets are common, so a gzip header alone doesn't mean the entry itself is compressed
+fn gunzips_to_size<R: Read + ?Sized>(reader: &mut R, uncompressed_size: u64) -> io::Result<bool> {
+    // Reading past the expected size makes the decoder check the trailer too
+    match io::copy(&mut GzDecoder::new(reader).take(uncompressed_size.saturating_add(1)), &mut io::sink()) {
+        Ok(out_size) => Ok(out_size == uncompressed_size),
+        Err(err) if matches!(err.kind(), io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof) => Ok(false),
+        Err(err) => Err(err)
+    }
+}
+
+pub(crate) fn check_entry_bounds(entry: &VtPackProcessedEntry, stored_size: u64, archive_size: u64) -> Result<()> {
+    if entry.file_data_abs_offset.checked_add(stored_size).is_none_or(|end| end > archive_size) {
+        return Err(VtPackError::EntryOutOfBounds {
+            path: entry.path.clone(),
+            offset: entry.file_data_abs_offset,
+            size: stored_size,
+            archive_size
+        });
+    }
+    Ok(())
+}
+
+// Only a guess from the head (the first SNIFF_SIZE bytes at most of the stored data), see check_compression
+pub(crate) fn sniff_entry_compression(size_pair: Option<(u64, u64)>, head: &[u8]) -> Option<VtPackCompressionInfo> {
+    let (stored_size, uncompressed_size) = size_pair?;
+    let compression = match VtPackCompression::sniff(head) {
+        // LZMA can't be decoded to check it, but its header has the uncompressed size
+        Some(VtPackCompression::Lzma) if u64::from_le_bytes(head[5..13].try_into().unwrap()) != uncompressed_size => return None,
+        Some(compression) => compression,
+        None if looks_like_raw_deflate(head) => VtPackCompression::Deflate,
+        None => return None
+    };
+    Some(VtPackCompressionInfo {
+        compression,
+        stored_size,
+        uncompressed_size
+    })
+}
+
+// Confirms a sniffed compression by decompressing all the stored data: compressions that can't be decoded can't be confirmed
+pub(crate) fn check_compression<R: Read + ?Sized>(reader: &mut R, info: &VtPackCompressionInfo) -> io::Result<bool> {
+    match info.compression {
+        VtPackCompression::Zlib => inflates_to_size(reader, true, info.uncompressed_size),
+        VtPackCompression::Deflate => inflates_to_size(reader, false, info.uncompressed_size),
+        VtPackCompression::Gzip => gunzips_to_size(reader, info.uncompressed_size),
+        _ => Ok(false)
+    }
+}
+
+// Out of bounds stored data is never sniffed
+fn sniff_stored_data<R: Seek + Read>(reader: &mut R, offset: u64, size_pair: Option<(u64, u64)>, archive_size: u64) -> io::Result<Option<VtPackCompressionInfo>> {
+    let Some((stored_size, _)) = size_pair else {
+        return Ok(None);
+    };
+    if offset.checked_add(stored_size).is_none_or(|end| end > archive_size) {
+        return Ok(None);
+    }
+
+    let mut head = Vec::new();
+    VtPackRawEntryReader::new(reader, offset, stored_size)?.take(stored_size.min(SNIFF_SIZE)).read_to_end(&mut head)?;
+    Ok(sniff_entry_compression(size_pair, &head))
+}
+
+// Compressed entries take the smaller size of their pair, anything else takes its whole file size
+// Not bounds-checked, except for the smaller size which is needed to detect the compression
+pub(crate) fn get_stored_size<R: Seek + Read>(reader: &mut R, offset: u64, file_size: u64, size_pair: Option<(u64, u64)>, archive_size: u64) -> io::Result<(u64, Option<VtPackCompressionInfo>)> {
+    if let Some(info) = sniff_stored_data(reader, offset, size_pair, archive_size)? {
+        if check_compression(&mut VtPackRawEntryReader::new(reader, offset, info.stored_size)?, &info)? {
+            return Ok((info.stored_size, Some(info)));
+        }
+    }
+    Ok((file_size, None))
+}
+
+impl VtPackFile {
+    // Compressions that can't be decoded are only a guess from their header, their entries are still read as stored
+    pub fn detect_compression<R: Seek + Read>(&self, reader: &mut R, entry: &VtPackProcessedEntry) -> Result<Option<VtPackCompressionInfo>> {
+        if !entry.is_file {
+            return Err(VtPackError::NotAFile(entry.path.clone()));
+        }
+
+        let archive_size = reader.seek(SeekFrom::End(0))?;
+        let Some(info) = sniff_stored_data(reader, entry.file_data_abs_offset, entry.compressed_size_pair, archive_size)? else {
+            return Ok(None);
+        };
+        if info.compression.is_supported() && !check_compression(&mut VtPackRawEntryReader::new(reader, entry.file_data_abs_offset, info.stored_size)?, &info)? {
+            return Ok(None);
+        }
+        Ok(Some(info))
+    }
+
+    // The size of the data actually stored for the entry, checked against the archive size
+    pub(crate) fn get_stored_data<R: Seek + Read>(&self, reader: &mut R, entry: &VtPackProcessedEntry) -> Result<(u64, Option<VtPackCompressionInfo>)> {
+        if !entry.is_file {
+            return Err(VtPackError::NotAFile(entry.path.clone()));
+        }
+
+        let archive_size = reader.seek(SeekFrom::End(0))?;
+        let (stored_size, info) = match self.raw_data {
+            true => (entry.file_size as u64, None),
+            false => get_stored_size(reader, entry.file_data_abs_offset, entry.file_size as u64, entry.compressed_size_pair, archive_size)?
+        };
+        check_entry_bounds(entry, stored_size, archive_size)?;
+        Ok((stored_size, info))
+    }
+
+    // The data exactly as stored, without any decompression
+    pub fn open_entry_raw<'a, R: Seek + Read>(&self, reader: &'a mut R, entry: &VtPackProcessedEntry) -> Result<VtPackRawEntryReader<'a, R>> {
+        let (stored_size, _) = self.get_stored_data(reader, entry)?;
+        Ok(VtPackRawEntryReader::new(reader, entry.file_data_abs_offset, stored_size)?)
+    }
+
+    // Detected compressed entries are decompressed on the fly, unless the archive was opened to read raw data
+    pub fn open_entry<'a, R: Seek + Read>(&self, reader: &'a mut R, entry: &VtPackProcessedEntry) -> Result<VtPackEntryReader<'a, R>> {
+        let (stored_size, info) = self.get_stored_data(reader, entry)?;
+        let raw_reader = VtPackRawEntryReader::new(reader, entry.file_data_abs_offset, stored_size)?;
+        let size = info.map(|info| info.uncompressed_size).unwrap_or(stored_size);
+        Ok(VtPackEntryReader::new(raw_reader, info.map(|info| info.compression), size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+    use flate2::{Compression, write::{DeflateEncoder, GzEncoder, ZlibEncoder}};
+    use super::*;
+    use crate::{VtPackReadOptions, tests::make_built_test_archive};
+
+    fn make_plain_data() -> Vec<u8> {
+        (0..2000).flat_map(|i| format!("line {} of some very compressible text\n", i % 37).into_bytes()).collect()
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn read_entry(archive: &[u8], path: &str, options: &VtPackReadOptions) -> Vec<u8> {
+        let mut reader = Cursor::new(archive);
+        let file = VtPackFile::new_with_options(&mut reader, options).unwrap();
+        let mut data = Vec::new();
+        file.open_entry(&mut reader, file.find(path).unwrap()).unwrap().read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn compressed_size_pairs() {
+        assert_eq!(get_compressed_size_pair(10, 30), Some((10, 30)));
+        assert_eq!(get_compressed_size_pair(30, 10), Some((10, 30)));
+        assert_eq!(get_compressed_size_pair(10, 10), None);
+        assert_eq!(get_compressed_size_pair(10, 0), None);
+        assert_eq!(get_compressed_size_pair(0, 10), None);
+        assert_eq!(get_compressed_size_pair(10, 10 * MAX_COMPRESSION_RATIO), Some((10, 10 * MAX_COMPRESSION_RATIO)));
+        assert_eq!(get_compressed_size_pair(10, 10 * MAX_COMPRESSION_RATIO + 10), None);
+    }
+
+    #[test]
+    fn sniff_headers() {
+        let plain = make_plain_data();
+        assert_eq!(VtPackCompression::sniff(&zlib(&plain)), Some(VtPackCompression::Zlib));
+        assert_eq!(VtPackCompression::sniff(&gzip(&plain)), Some(VtPackCompression::Gzip));
+        assert_eq!(VtPackCompression::sniff(&[0x28, 0xB5, 0x2F, 0xFD, 0x00]), Some(VtPackCompression::Zstd));
+        assert_eq!(VtPackCompression::sniff(&[0x04, 0x22, 0x4D, 0x18, 0x00]), Some(VtPackCompression::Lz4));
+        assert_eq!(VtPackCompression::sniff(b"\xFD7zXZ\x00\x00"), Some(VtPackCompression::Xz));
+        assert_eq!(VtPackCompression::sniff(&[0x5D, 0x00, 0x00, 0x10, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]), Some(VtPackCompression::Lzma));
+        assert_eq!(VtPackCompression::sniff(&[0x5D, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]), None);
+        assert_eq!(VtPackCompression::sniff(&plain), None);
+        assert_eq!(VtPackCompression::sniff(&deflate(&plain)), None);
+        assert_eq!(VtPackCompression::sniff(&[]), None);
+    }
+
+    #[test]
+    fn inflate_checks() {
+        let plain = make_plain_data();
+        let size = plain.len() as u64;
+        let (zlib_data, deflate_data, gzip_data) = (zlib(&plain), deflate(&plain), gzip(&plain));
+
+        assert!(inflates_to_size(&mut zlib_data.as_slice(), true, size).unwrap());
+        assert!(!inflates_to_size(&mut zlib_data.as_slice(), true, size - 1).unwrap());
+        assert!(!inflates_to_size(&mut zlib_data.as_slice(), true, size + 1).unwrap());
+        assert!(!inflates_to_size(&mut &zlib_data[..zlib_data.len() / 2], true, size).unwrap());
+        assert!(!inflates_to_size(&mut zlib_data.as_slice(), false, size).unwrap());
+
+        assert!(inflates_to_size(&mut deflate_data.as_slice(), false, size).unwrap());
+        assert!(!inflates_to_size(&mut &deflate_data[..deflate_data.len() / 2], false, size).unwrap());
+        assert!(!inflates_to_size(&mut plain.as_slice(), false, size).unwrap());
+
+        assert!(gunzips_to_size(&mut gzip_data.as_slice(), size).unwrap());
+        assert!(!gunzips_to_size(&mut gzip_data.as_slice(), size - 1).unwrap());
+        assert!(!gunzips_to_size(&mut gzip_data.as_slice(), size + 1).unwrap());
+        assert!(!gunzips_to_size(&mut &gzip_data[..gzip_data.len() / 2], size).unwrap());
+        assert!(!gunzips_to_size(&mut plain.as_slice(), size).unwrap());
+    }
+
+    #[test]
+    fn read_compressed_entries() {
+        let plain = make_plain_data();
+        let size = plain.len() as u64;
+        let (zlib_data, deflate_data, gzip_data) = (zlib(&plain), deflate(&plain), gzip(&plain));
+        let archive = make_built_test_archive(&[
+            ("zlib.bin", zlib_data.clone(), size),
+            ("deflate.bin", deflate_data.clone(), size),
+            ("gzip.bin", gzip_data.clone(), size)
+        ]);
+
+        let mut reader = Cursor::new(&archive);
+        let file = VtPackFile::new(&mut reader).unwrap();
+        for (path, compression, stored_data) in [("zlib.bin", VtPackCompression::Zlib, &zlib_data), ("deflate.bin", VtPackCompression::Deflate, &deflate_data), ("gzip.bin", VtPackCompression::Gzip, &gzip_data)] {
+            let entry = file.find(path).unwrap();
+            let info = file.detect_compression(&mut reader, entry).unwrap().unwrap();
+            assert_eq!(info, VtPackCompressionInfo {
+                compression,
+                stored_size: stored_data.len() as u64,
+                uncompressed_size: size
+            });
+            assert_eq!(read_entry(&archive, path, &VtPackReadOptions::new()), plain, "{}", path);
+
+            let mut raw_data = Vec::new();
+            file.open_entry_raw(&mut reader, entry).unwrap().read_to_end(&mut raw_data).unwrap();
+            assert_eq!(&raw_data, stored_data);
+            assert_eq!(&read_entry(&archive, path, &VtPackReadOptions::new().raw_data(true)), stored_data);
+        }
+    }
+
+    #[test]
+    fn seek_compressed_entry() {
+        let plain = make_plain_data();
+        let size = plain.len() as u64;
+        let archive = make_built_test_archive(&[("zlib.bin", zlib(&plain), size)]);
+        let mut reader = Cursor::new(&archive);
+        let file = VtPackFile::new(&mut reader).unwrap();
+        let mut entry_reader = file.open_entry(&mut reader, file.find("zlib.bin").unwrap()).unwrap();
+        assert!(entry_reader.is_compressed());
+
+        let mut data = vec![0; 16];
+        assert_eq!(entry_reader.seek(SeekFrom::Start(1000)).unwrap(), 1000);
+        entry_reader.read_exact(&mut data).unwrap();
+        assert_eq!(data, plain[1000..1016]);
+        assert_eq!(entry_reader.seek(SeekFrom::Current(-100)).unwrap(), 916);
+        entry_reader.read_exact(&mut data).unwrap();
+        assert_eq!(data, plain[916..932]);
+        assert_eq!(entry_reader.seek(SeekFrom::End(-16)).unwrap(), size - 16);
+        entry_reader.read_exact(&mut data).unwrap();
+        assert_eq!(data, plain[plain.len() - 16..]);
+
+        // Past the end, twice, then back
+        assert_eq!(entry_reader.seek(SeekFrom::Start(size + 1000)).unwrap(), size + 1000);
+        assert_eq!(entry_reader.seek(SeekFrom::Start(size + 2000)).unwrap(), size + 2000);
+        assert_eq!(entry_reader.read(&mut data).unwrap(), 0);
+        assert_eq!(entry_reader.seek(SeekFrom::Start(5)).unwrap(), 5);
+        entry_reader.read_exact(&mut data).unwrap();
+        assert_eq!(data, plain[5..21]);
+        assert!(entry_reader.seek(SeekFrom::Current(-100)).is_err());
+    }
+
+    #[test]
+    fn stored_lookalikes_stay_stored() {
+        let plain = make_plain_data();
+        let gzip_asset = gzip(&plain);
+        let gzip_asset_size = gzip_asset.len() as u64;
+        let zstd_asset = [&[0x28, 0xB5, 0x2F, 0xFD][..], &plain[..100]].concat();
+        let zlib_like_text = b"x\x9C is also how this text file starts".to_vec();
+        let entries = [
+            // The gzip header is at the start of the smaller size too, but that isn't a whole gzip stream
+            ("asset.gz", gzip_asset.clone(), gzip_asset_size / 2),
+            ("asset_bigger_unk2.gz", gzip_asset.clone(), gzip_asset_size * 2),
+            ("asset.zst", zstd_asset.clone(), zstd_asset.len() as u64 * 3),
+            ("asset_smaller_unk2.zst", zstd_asset.clone(), 10),
+            ("text.txt", zlib_like_text.clone(), 3 * zlib_like_text.len() as u64),
+            ("plain.bin", plain.clone(), plain.len() as u64 * 2)
+        ];
+        let archive = make_built_test_archive(&entries);
+
+        let mut reader = Cursor::new(&archive);
+        let file = VtPackFile::new(&mut reader).unwrap();
+        for (path, data, _) in entries.iter() {
+            assert_eq!(&read_entry(&archive, path, &VtPackReadOptions::new()), data, "{}", path);
+            assert_eq!(file.open_entry(&mut reader, file.find(path).unwrap()).unwrap().get_compression(), None, "{}", path);
+        }
+
+        // Compressions that can't be decoded are still reported as a guess
+        assert_eq!(file.detect_compression(&mut reader, file.find("asset.zst").unwrap()).unwrap().map(|info| info.compression), Some(VtPackCompression::Zstd));
+        assert_eq!(file.detect_compression(&mut reader, file.find("asset.gz").unwrap()).unwrap(), None);
+    }
+}
@@ -0,0 +1,106 @@
+use std::{fmt, io, path::{Path, PathBuf}};
+
+#[derive(Debug)]
+pub enum VtPackError {
+    InvalidStringOffset(u32),
+    UnterminatedString(u32),
+    NotAFile(String),
+    EntryNotFound(String),
+    InvalidPattern(String, String),
+    UnknownProfile(String),
+    UnknownVersion(u32),
+    OutputExists(PathBuf),
+    UnsafePath {
+        path: String,
+        reason: &'static str
+    },
+    InvalidEntryPath {
+        path: String,
+        reason: &'static str
+    },
+    UnsafeCleanTarget {
+        path: PathBuf,
+        reason: &'static str
+    },
+    EntryOutOfBounds {
+        path: String,
+        offset: u64,
+        size: u64,
+        archive_size: u64
+    },
+    DataAlreadyPassed {
+        path: String,
+        offset: u64,
+        stream_pos: u64
+    },
+    IntegrityMismatch {
+        path: String,
+        check: String,
+        expected: u64,
+        actual: u64
+    },
+    Io(io::Error),
+    PathIo(PathBuf, io::Error),
+    Parse(binrw::Error)
+}
+
+pub type Result<T> = std::result::Result<T, VtPackError>;
+
+impl fmt::Display for VtPackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidStringOffset(offset) => write!(f, "string table offset {:#X} is out of bounds", offset),
+            Self::UnterminatedString(offset) => write!(f, "string at string table offset {:#X} is not NUL-terminated", offset),
+            Self::NotAFile(path) => write!(f, "entry '{}' is not a file", path),
+            Self::EntryNotFound(path) => write!(f, "entry '{}' was not found", path),
+            Self::InvalidPattern(pattern, err) => write!(f, "invalid pattern '{}': {}", pattern, err),
+            Self::UnknownVersion(version) => write!(f, "unknown vtPack version {:#X}", version),
+            Self::UnknownProfile(name) => write!(f, "no profile named '{}'", name),
+            Self::OutputExists(path) => write!(f, "output path '{}' already exists", path.display()),
+            Self::UnsafePath { path, reason } => write!(f, "entry path '{}' is unsafe to extract: {}", path, reason),
+            Self::InvalidEntryPath { path, reason } => write!(f, "can't add entry '{}': {}", path, reason),
+            Self::UnsafeCleanTarget { path, reason } => write!(f, "refusing to clean output path '{}': {}", path.display(), reason),
+            Self::EntryOutOfBounds { path, offset, size, archive_size } => write!(f, "entry '{}' (offset {:#X}, size {:#X}) is out of bounds of the archive (size {:#X})", path, offset, size, archive_size),
+            Self::DataAlreadyPassed { path, offset, stream_pos } => write!(f, "entry '{}' data (offset {:#X}) was already passed in the input stream (now at {:#X})", path, offset, stream_pos),
+            Self::IntegrityMismatch { path, check, expected, actual } => write!(f, "entry '{}' failed integrity check {}: expected {:#X}, got {:#X}", path, check, expected, actual),
+            Self::Io(err) => write!(f, "I/O error: {}", err),
+            Self::PathIo(path, err) => write!(f, "I/O error at '{}': {}", path.display(), err),
+            Self::Parse(err) => write!(f, "parse error: {}", err)
+        }
+    }
+}
+
+impl std::error::Error for VtPackError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) | Self::PathIo(_, err) => Some(err),
+            Self::Parse(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for VtPackError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<binrw::Error> for VtPackError {
+    fn from(err: binrw::Error) -> Self {
+        match err {
+            binrw::Error::Io(err) => Self::Io(err),
+            err => Self::Parse(err)
+        }
+    }
+}
+
+pub(crate) trait IoResultExt<T> {
+    fn with_path<P: AsRef<Path>>(self, path: P) -> Result<T>;
+}
+
+impl<T> IoResultExt<T> for io::Result<T> {
+    fn with_path<P: AsRef<Path>>(self, path: P) -> Result<T> {
+        self.map_err(|err| VtPackError::PathIo(path.as_ref().to_path_buf(), err))
+    }
+}
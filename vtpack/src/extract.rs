@@ -0,0 +1,286 @@
+use std::{collections::BTreeSet, io::{self, Read, Seek}, path::{Path, PathBuf}};
+use crate::{VtPackFile, VtPackProcessedEntry, VtPackEntryFilter, VtPackError, Result, IoResultExt};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum VtPackOverwritePolicy {
+    #[default]
+    Overwrite,
+    SkipExisting,
+    FailOnConflict
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VtPackExtractOptions {
+    pub clean_target: bool,
+    pub overwrite_policy: VtPackOverwritePolicy,
+    pub dry_run: bool,
+    pub worker_count: usize,
+    pub protected_paths: Vec<PathBuf>
+}
+
+impl VtPackExtractOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Removes the whole output directory before extracting: this must always be explicitly requested
+    pub fn clean_target(mut self, clean_target: bool) -> Self {
+        self.clean_target = clean_target;
+        self
+    }
+
+    pub fn overwrite_policy(mut self, overwrite_policy: VtPackOverwritePolicy) -> Self {
+        self.overwrite_policy = overwrite_policy;
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    // Cleaning the output directory is refused if it contains this path (like the archive being extracted)
+    pub fn protect_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.protected_paths.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    // Only used for parallel extraction, 0 means as many workers as the system can run at once
+    pub fn worker_count(mut self, worker_count: usize) -> Self {
+        self.worker_count = worker_count;
+        self
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum VtPackExtractAction {
+    CreateDir,
+    Write,
+    Overwrite,
+    Skip
+}
+
+#[derive(Clone, Debug)]
+pub struct VtPackExtractedEntry {
+    pub(crate) entry_index: usize,
+    pub(crate) out_path: PathBuf,
+    pub(crate) action: VtPackExtractAction
+}
+
+impl VtPackExtractedEntry {
+    pub(crate) fn writes_file(&self) -> bool {
+        matches!(self.action, VtPackExtractAction::Write | VtPackExtractAction::Overwrite)
+    }
+
+    pub fn get_entry_index(&self) -> usize {
+        self.entry_index
+    }
+
+    pub fn get_out_path(&self) -> &PathBuf {
+        &self.out_path
+    }
+
+    pub fn get_action(&self) -> VtPackExtractAction {
+        self.action
+    }
+}
+
+#[derive(Debug)]
+pub struct VtPackFailedEntry {
+    pub(crate) entry_index: usize,
+    pub(crate) error: VtPackError
+}
+
+impl VtPackFailedEntry {
+    pub fn get_entry_index(&self) -> usize {
+        self.entry_index
+    }
+
+    pub fn get_error(&self) -> &VtPackError {
+        &self.error
+    }
+}
+
+// In dry-run mode, this describes what would have been done
+#[derive(Clone, Debug, Default)]
+pub struct VtPackExtractReport {
+    pub(crate) dry_run: bool,
+    pub(crate) cleaned_target: bool,
+    pub(crate) entries: Vec<VtPackExtractedEntry>
+}
+
+impl VtPackExtractReport {
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    pub fn cleaned_target(&self) -> bool {
+        self.cleaned_target
+    }
+
+    pub fn get_entries(&self) -> &Vec<VtPackExtractedEntry> {
+        &self.entries
+    }
+
+    pub fn count_action(&self, action: VtPackExtractAction) -> usize {
+        self.entries.iter().filter(|entry| entry.action == action).count()
+    }
+}
+
+impl VtPackFile {
+    // The current directory, any of its parents and anything containing a protected path are never removed
+    fn check_clean_target(out_path: &Path, options: &VtPackExtractOptions) -> Result<()> {
+        let unsafe_target = |reason| Err(VtPackError::UnsafeCleanTarget {
+            path: out_path.to_path_buf(),
+            reason
+        });
+
+        let out_path = out_path.canonicalize().with_path(out_path)?;
+        let cur_dir = std::env::current_dir().and_then(|cur_dir| cur_dir.canonicalize())?;
+        if cur_dir.starts_with(&out_path) {
+            return unsafe_target("it is the current directory or one of its parents");
+        }
+        for protected_path in options.protected_paths.iter() {
+            // Protected paths that don't exist have nothing to lose
+            if protected_path.canonicalize().is_ok_and(|protected_path| protected_path.starts_with(&out_path)) {
+                return unsafe_target("it contains the input archive or another protected path");
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn clean_target(out_path: &Path, options: &VtPackExtractOptions) -> Result<bool> {
+        if !options.clean_target || !out_path.exists() {
+            return Ok(false);
+        }
+
+        Self::check_clean_target(out_path, options)?;
+
+        if !options.dry_run {
+            std::fs::remove_dir_all(out_path).with_path(out_path)?;
+        }
+        Ok(true)
+    }
+
+    pub(crate) fn get_extract_action(entry: &VtPackProcessedEntry, full_path: &Path, cleaned_target: bool, options: &VtPackExtractOptions) -> Result<VtPackExtractAction> {
+        // A cleaned target has nothing left in it, even if this is just a dry run
+        let exists = !cleaned_target && full_path.exists();
+        if entry.is_dir() {
+            Ok(VtPackExtractAction::CreateDir)
+        }
+        else if !exists {
+            Ok(VtPackExtractAction::Write)
+        }
+        else {
+            match options.overwrite_policy {
+                VtPackOverwritePolicy::Overwrite => Ok(VtPackExtractAction::Overwrite),
+                VtPackOverwritePolicy::SkipExisting => Ok(VtPackExtractAction::Skip),
+                VtPackOverwritePolicy::FailOnConflict => Err(VtPackError::OutputExists(full_path.to_path_buf()))
+            }
+        }
+    }
+
+    // Plans every matching entry and creates all the directories, so that files can then be written in any order
+    // Nothing is touched before everything is planned, so conflicts and unsafe paths fail without writing anything
+    // When collecting failures, only entries whose directory can't be created are left out and returned apart
+    pub(crate) fn plan_export(&self, out_path: &Path, filter: &VtPackEntryFilter, options: &VtPackExtractOptions, collect_failures: bool) -> Result<(VtPackExtractReport, Vec<VtPackFailedEntry>)> {
+        let will_clean_target = options.clean_target && out_path.exists();
+        let mut entries = Vec::new();
+        for (entry_idx, entry) in self.p_entries.iter().enumerate().filter(|(_, entry)| filter.matches(entry)) {
+            let full_path = out_path.join(entry.get_safe_path()?);
+            let action = Self::get_extract_action(entry, &full_path, will_clean_target, options)?;
+            entries.push(VtPackExtractedEntry {
+                entry_index: entry_idx,
+                out_path: full_path,
+                action
+            });
+        }
+
+        let mut report = VtPackExtractReport {
+            dry_run: options.dry_run,
+            cleaned_target: Self::clean_target(out_path, options)?,
+            entries
+        };
+        let mut failed = Vec::new();
+        if !options.dry_run {
+            std::fs::create_dir_all(out_path).with_path(out_path)?;
+
+            let mut dir_paths = BTreeSet::new();
+            for extracted_entry in report.entries.iter() {
+                match extracted_entry.action {
+                    VtPackExtractAction::CreateDir => dir_paths.insert(extracted_entry.out_path.as_path()),
+                    VtPackExtractAction::Write | VtPackExtractAction::Overwrite => dir_paths.insert(extracted_entry.out_path.parent().unwrap_or(out_path)),
+                    VtPackExtractAction::Skip => false
+                };
+            }
+
+            let mut failed_dir_paths = BTreeSet::new();
+            for dir_path in dir_paths {
+                let Err(err) = std::fs::create_dir_all(dir_path) else {
+                    continue;
+                };
+                if !collect_failures {
+                    return Err(err).with_path(dir_path);
+                }
+
+                // Files inside this directory will fail on their own when written
+                failed_dir_paths.insert(dir_path.to_path_buf());
+                for extracted_entry in report.entries.iter().filter(|extracted_entry| extracted_entry.action == VtPackExtractAction::CreateDir && extracted_entry.out_path == dir_path) {
+                    failed.push(VtPackFailedEntry {
+                        entry_index: extracted_entry.entry_index,
+                        error: VtPackError::PathIo(dir_path.to_path_buf(), io::Error::new(err.kind(), err.to_string()))
+                    });
+                }
+            }
+            report.entries.retain(|extracted_entry| extracted_entry.action != VtPackExtractAction::CreateDir || !failed_dir_paths.contains(&extracted_entry.out_path));
+        }
+
+        Ok((report, failed))
+    }
+
+    pub(crate) fn prepare_export(&self, out_path: &Path, filter: &VtPackEntryFilter, options: &VtPackExtractOptions) -> Result<VtPackExtractReport> {
+        let (report, _) = self.plan_export(out_path, filter, options, false)?;
+        Ok(report)
+    }
+
+    // Parent directories of matching entries are created as needed, even if the directory entries themselves don't match
+    pub fn export<R: Seek + Read, P: AsRef<Path>>(&self, reader: &mut R, out_path: P, filter: &VtPackEntryFilter, options: &VtPackExtractOptions) -> Result<VtPackExtractReport> {
+        let report = self.prepare_export(out_path.as_ref(), filter, options)?;
+        for extracted_entry in report.entries.iter().filter(|extracted_entry| extracted_entry.writes_file()) {
+            let entry = &self.p_entries[extracted_entry.entry_index];
+            if options.dry_run {
+                self.check_entry_file(reader, entry)?;
+            }
+            else {
+                self.write_entry_file(reader, entry, &extracted_entry.out_path)?;
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuse_unsafe_clean_targets() {
+        let options = VtPackExtractOptions::new().clean_target(true).dry_run(true);
+        let cur_dir = std::env::current_dir().unwrap();
+        for out_path in [Path::new("."), cur_dir.as_path(), cur_dir.parent().unwrap()] {
+            assert!(matches!(VtPackFile::clean_target(out_path, &options), Err(VtPackError::UnsafeCleanTarget { .. })), "'{}' was not refused", out_path.display());
+        }
+
+        let out_path = std::env::temp_dir().join(format!("vtpack-clean-test-{}", std::process::id()));
+        std::fs::create_dir_all(out_path.join("sub")).unwrap();
+        std::fs::write(out_path.join("sub").join("archive.vpk"), b"").unwrap();
+        let protected_options = options.clone().protect_path(out_path.join("sub").join("archive.vpk"));
+        assert!(matches!(VtPackFile::clean_target(&out_path, &protected_options), Err(VtPackError::UnsafeCleanTarget { .. })));
+
+        let options = options.dry_run(false);
+        assert!(VtPackFile::clean_target(&out_path, &options.clone().protect_path(std::env::temp_dir().join("vtpack-missing.vpk"))).unwrap());
+        assert!(!out_path.exists());
+        assert!(!VtPackFile::clean_target(&out_path, &options).unwrap());
+    }
+}
@@ -0,0 +1,150 @@
+use glob::{MatchOptions, Pattern};
+use regex::{Regex, RegexBuilder};
+use crate::{VtPackProcessedEntry, VtPackError, Result, path::make_lookup_key};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum VtPackEntryKind {
+    File,
+    Dir
+}
+
+pub enum VtPackPattern {
+    Glob(Pattern),
+    Regex(Regex)
+}
+
+impl VtPackPattern {
+    pub fn glob<S: AsRef<str>>(pattern: S) -> Result<Self> {
+        let pattern = make_lookup_key(pattern.as_ref(), false);
+        Pattern::new(&pattern).map(Self::Glob).map_err(|err| VtPackError::InvalidPattern(pattern, err.to_string()))
+    }
+
+    pub fn regex<S: AsRef<str>>(pattern: S, ignore_case: bool) -> Result<Self> {
+        let pattern = pattern.as_ref();
+        RegexBuilder::new(pattern).case_insensitive(ignore_case).build().map(Self::Regex).map_err(|err| VtPackError::InvalidPattern(pattern.to_string(), err.to_string()))
+    }
+
+    // Matches "path" itself and anything below it
+    pub fn prefix<S: AsRef<str>>(path: S, ignore_case: bool) -> Result<Self> {
+        let path = make_lookup_key(path.as_ref(), false);
+        if path.is_empty() {
+            Self::glob("**")
+        }
+        else {
+            Self::regex(format!("^{}(/|$)", regex::escape(&path)), ignore_case)
+        }
+    }
+
+    // Paths are always matched in their normalized "dir/dir/name" form, without a leading separator
+    pub fn matches(&self, path: &str, ignore_case: bool) -> bool {
+        let path = make_lookup_key(path, false);
+        match self {
+            Self::Glob(pattern) => pattern.matches_with(&path, MatchOptions {
+                case_sensitive: !ignore_case,
+                require_literal_separator: true,
+                require_literal_leading_dot: false
+            }),
+            // Regexes carry their own case sensitivity
+            Self::Regex(regex) => regex.is_match(&path)
+        }
+    }
+}
+
+pub type VtPackEntryPredicate = Box<dyn Fn(&VtPackProcessedEntry) -> bool + Send + Sync>;
+
+#[derive(Default)]
+pub struct VtPackEntryFilter {
+    include: Vec<VtPackPattern>,
+    exclude: Vec<VtPackPattern>,
+    kind: Option<VtPackEntryKind>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    predicates: Vec<VtPackEntryPredicate>,
+    ignore_case: bool
+}
+
+impl VtPackEntryFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn include(mut self, pattern: VtPackPattern) -> Self {
+        self.include.push(pattern);
+        self
+    }
+
+    pub fn exclude(mut self, pattern: VtPackPattern) -> Self {
+        self.exclude.push(pattern);
+        self
+    }
+
+    pub fn include_glob<S: AsRef<str>>(self, pattern: S) -> Result<Self> {
+        Ok(self.include(VtPackPattern::glob(pattern)?))
+    }
+
+    pub fn exclude_glob<S: AsRef<str>>(self, pattern: S) -> Result<Self> {
+        Ok(self.exclude(VtPackPattern::glob(pattern)?))
+    }
+
+    pub fn include_regex<S: AsRef<str>>(self, pattern: S) -> Result<Self> {
+        let ignore_case = self.ignore_case;
+        Ok(self.include(VtPackPattern::regex(pattern, ignore_case)?))
+    }
+
+    pub fn exclude_regex<S: AsRef<str>>(self, pattern: S) -> Result<Self> {
+        let ignore_case = self.ignore_case;
+        Ok(self.exclude(VtPackPattern::regex(pattern, ignore_case)?))
+    }
+
+    pub fn kind(mut self, kind: VtPackEntryKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn min_size(mut self, size: u64) -> Self {
+        self.min_size = Some(size);
+        self
+    }
+
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    pub fn predicate<F: Fn(&VtPackProcessedEntry) -> bool + Send + Sync + 'static>(mut self, predicate: F) -> Self {
+        self.predicates.push(Box::new(predicate));
+        self
+    }
+
+    // Set this before adding regexes, since they are compiled with the case sensitivity in effect at that point
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    // No include patterns means everything is included; excludes always win over includes
+    pub fn matches(&self, entry: &VtPackProcessedEntry) -> bool {
+        let path = entry.get_path();
+
+        if !self.include.is_empty() && !self.include.iter().any(|pattern| pattern.matches(path, self.ignore_case)) {
+            return false;
+        }
+        if self.exclude.iter().any(|pattern| pattern.matches(path, self.ignore_case)) {
+            return false;
+        }
+
+        match self.kind {
+            Some(VtPackEntryKind::File) if !entry.is_file() => return false,
+            Some(VtPackEntryKind::Dir) if !entry.is_dir() => return false,
+            _ => {}
+        }
+
+        // Size limits only make sense for files
+        let file_size = entry.get_file_size() as u64;
+        if entry.is_file() && (self.min_size.is_some_and(|size| file_size < size) || self.max_size.is_some_and(|size| file_size > size)) {
+            return false;
+        }
+
+        self.predicates.iter().all(|predicate| predicate(entry))
+    }
+}
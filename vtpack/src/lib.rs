@@ -1,16 +1,130 @@
-use std::{io::{Seek, Read, Write}, ffi::CStr, fs::{File, OpenOptions}, path::Path};
-use binrw::{BinRead, BinWrite, io::{SeekFrom, BufReader}, BinResult};
+use std::{fmt, io::{self, Seek, Read, Write, BufWriter}, ffi::CStr, fs::{File, OpenOptions}, path::{Path, PathBuf}, collections::HashMap, time::SystemTime};
+use binrw::{BinRead, BinWrite, BinResult, Endian, io::{SeekFrom, BufReader}};
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, BinRead, BinWrite)]
-#[repr(u32)]
-#[brw(repr = u32)]
+mod error;
+pub use error::*;
+
+mod path;
+use path::*;
+
+mod builder;
+pub use builder::*;
+
+mod reader;
+pub use reader::*;
+
+mod archive;
+pub use archive::*;
+
+mod tree;
+pub use tree::*;
+
+mod filter;
+pub use filter::*;
+
+mod extract;
+pub use extract::*;
+
+mod validate;
+pub use validate::*;
+
+mod research;
+pub use research::*;
+
+mod checksum;
+pub use checksum::*;
+
+mod timestamp;
+pub use timestamp::*;
+
+mod compression;
+pub use compression::*;
+
+mod profile;
+pub use profile::*;
+
+mod repack;
+
+mod shared;
+pub use shared::*;
+
+mod parallel;
+pub use parallel::*;
+
+mod ordered;
+
+mod stream;
+pub use stream::*;
+
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "mmap")]
+pub use mmap::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum VtPackVersion {
-    Ver1 = 1,
-    Ver2 = 2
+    Ver1,
+    Ver2,
+    Unknown(u32)
+}
+
+impl VtPackVersion {
+    pub fn from_raw(raw_version: u32) -> Self {
+        match raw_version {
+            1 => Self::Ver1,
+            2 => Self::Ver2,
+            raw_version => Self::Unknown(raw_version)
+        }
+    }
+
+    pub fn get_raw(&self) -> u32 {
+        match self {
+            Self::Ver1 => 1,
+            Self::Ver2 => 2,
+            Self::Unknown(raw_version) => *raw_version
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Self::Unknown(_))
+    }
+
+    // Unknown versions are parsed with the closest known layout, hoping newer versions only grow
+    pub fn get_layout(&self) -> Self {
+        match self {
+            Self::Unknown(0) => Self::Ver1,
+            Self::Unknown(_) => Self::Ver2,
+            version => *version
+        }
+    }
+}
+
+impl fmt::Display for VtPackVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(raw_version) => write!(f, "unknown ({:#X}, read as {})", raw_version, self.get_layout()),
+            version => write!(f, "{}", version.get_raw())
+        }
+    }
+}
+
+impl BinRead for VtPackVersion {
+    type Args<'a> = ();
+
+    fn read_options<R: Read + Seek>(reader: &mut R, endian: Endian, _args: Self::Args<'_>) -> BinResult<Self> {
+        u32::read_options(reader, endian, ()).map(Self::from_raw)
+    }
+}
+
+impl BinWrite for VtPackVersion {
+    type Args<'a> = ();
+
+    fn write_options<W: Write + Seek>(&self, writer: &mut W, endian: Endian, _args: Self::Args<'_>) -> BinResult<()> {
+        self.get_raw().write_options(writer, endian, ())
+    }
 }
 
 #[derive(Clone, Debug, BinRead, BinWrite)]
-#[br(little)]
 pub struct VtPackStringTable {
     pub table_size: u32,
     #[br(count = table_size)]
@@ -18,7 +132,6 @@ pub struct VtPackStringTable {
 }
 
 #[derive(Clone, Debug, BinRead, BinWrite)]
-#[br(little)]
 pub struct VtPackRawEntryHeader {
     pub path_name_str_table_offset: u32,
     pub path_dir_str_table_offset: u32,
@@ -32,11 +145,27 @@ pub struct VtPackRawEntryHeader {
 
 pub const INVALID_STRING_TABLE_OFFSET: u32 = u32::MAX;
 
+pub const VTPACK_MAGIC: &[u8; 6] = b"vtPack";
+pub const RAW_ENTRY_HEADER_SIZE: u64 = 44;
+
+pub fn get_raw_header_size(version: VtPackVersion) -> u64 {
+    match version.get_layout() {
+        VtPackVersion::Ver1 => 34,
+        _ => 46
+    }
+}
+
+const COPY_CHUNK_SIZE: usize = 0x100000;
+
 pub struct VtPackProcessedEntry {
     is_file: bool,
     path: String,
+    dir_str: String,
+    name_str: String,
     file_size: usize,
-    file_data_abs_offset: u64
+    file_data_abs_offset: u64,
+    modified_time: Option<SystemTime>,
+    compressed_size_pair: Option<(u64, u64)>
 }
 
 impl VtPackProcessedEntry {
@@ -44,6 +173,14 @@ impl VtPackProcessedEntry {
         &self.path
     }
 
+    pub fn get_raw_dir(&self) -> &String {
+        &self.dir_str
+    }
+
+    pub fn get_raw_name(&self) -> &String {
+        &self.name_str
+    }
+
     pub fn is_file(&self) -> bool {
         self.is_file
     }
@@ -55,127 +192,493 @@ impl VtPackProcessedEntry {
     pub fn get_file_size(&self) -> usize {
         self.file_size
     }
+
+    pub fn get_file_data_abs_offset(&self) -> u64 {
+        self.file_data_abs_offset
+    }
+
+    // Only available with a timestamp decoder set
+    pub fn get_modified_time(&self) -> Option<SystemTime> {
+        self.modified_time
+    }
+
+    pub fn get_safe_path(&self) -> Result<PathBuf> {
+        let safe_path = sanitize_entry_path(&self.path)?;
+        if self.is_file && safe_path.as_os_str().is_empty() {
+            return Err(VtPackError::UnsafePath {
+                path: self.path.clone(),
+                reason: "is empty"
+            });
+        }
+        Ok(safe_path)
+    }
 }
 
 #[derive(Clone, Debug, BinRead, BinWrite)]
-#[br(little, magic = b"vtPack")]
-pub struct VtPackRawFile {
+#[brw(magic = b"vtPack")]
+pub struct VtPackRawHeader {
     pub version: VtPackVersion,
     pub unk1: u32,
     pub unk2: u32,
 
-    #[br(if(version == VtPackVersion::Ver1))]
+    #[brw(if(version.get_layout() == VtPackVersion::Ver1))]
     pub unk3_v1: u32,
-    #[br(if(version == VtPackVersion::Ver2))]
+    #[brw(if(version.get_layout() == VtPackVersion::Ver2))]
     pub unk3_v2: u64,
     
-    #[br(if(version == VtPackVersion::Ver1))]
+    #[brw(if(version.get_layout() == VtPackVersion::Ver1))]
     pub unk4_v1: u32,
-    #[br(if(version == VtPackVersion::Ver2))]
+    #[brw(if(version.get_layout() == VtPackVersion::Ver2))]
     pub unk4_v2: u64,
     
     pub entry_count: u32,
 
-    #[br(if(version == VtPackVersion::Ver1))]
+    #[brw(if(version.get_layout() == VtPackVersion::Ver1))]
     pub str_table_abs_offset_v1: u32,
-    #[br(if(version == VtPackVersion::Ver2))]
-    pub str_table_abs_offset_v2: u64,
+    #[brw(if(version.get_layout() == VtPackVersion::Ver2))]
+    pub str_table_abs_offset_v2: u64
+}
+
+impl VtPackRawHeader {
+    // Unknown fields are zeroed
+    pub fn new(version: VtPackVersion, entry_count: u32, str_table_abs_offset: u64) -> Self {
+        let mut header = Self {
+            version,
+            unk1: 0,
+            unk2: 0,
+            unk3_v1: 0,
+            unk3_v2: 0,
+            unk4_v1: 0,
+            unk4_v2: 0,
+            entry_count,
+            str_table_abs_offset_v1: 0,
+            str_table_abs_offset_v2: 0
+        };
+        header.set_str_table_abs_offset(str_table_abs_offset);
+        header
+    }
+
+    // Byte-swapped known versions (like 0x01000000) mean the whole archive is big-endian
+    pub fn detect_endian<R: Seek + Read>(reader: &mut R) -> Result<Endian> {
+        let start_pos = reader.stream_position()?;
+        let mut head = [0u8; VTPACK_MAGIC.len() + 4];
+        reader.read_exact(&mut head)?;
+        reader.seek(SeekFrom::Start(start_pos))?;
+
+        let raw_version: [u8; 4] = head[VTPACK_MAGIC.len()..].try_into().unwrap();
+        let le_version = VtPackVersion::from_raw(u32::from_le_bytes(raw_version));
+        let be_version = VtPackVersion::from_raw(u32::from_be_bytes(raw_version));
+        if !le_version.is_known() && be_version.is_known() {
+            Ok(Endian::Big)
+        }
+        else {
+            Ok(Endian::Little)
+        }
+    }
+
+    pub fn get_size(&self) -> u64 {
+        get_raw_header_size(self.version)
+    }
+
+    pub fn get_str_table_abs_offset(&self) -> u64 {
+        match self.version.get_layout() {
+            VtPackVersion::Ver1 => self.str_table_abs_offset_v1 as u64,
+            _ => self.str_table_abs_offset_v2
+        }
+    }
+
+    // v1 offsets are truncated to 32 bits
+    pub fn set_str_table_abs_offset(&mut self, str_table_abs_offset: u64) {
+        match self.version.get_layout() {
+            VtPackVersion::Ver1 => self.str_table_abs_offset_v1 = str_table_abs_offset as u32,
+            _ => self.str_table_abs_offset_v2 = str_table_abs_offset
+        }
+    }
+
+    // The entry headers come right after the string table
+    pub fn get_entries_abs_offset(&self, str_table: &VtPackStringTable) -> u64 {
+        self.get_str_table_abs_offset() + 4 + str_table.table_size as u64
+    }
+}
 
-    // Ugly, but does the trick
-    #[br(seek_before = SeekFrom::Start(str_table_abs_offset_v2.max(str_table_abs_offset_v1 as u64)))]
+#[derive(Clone, Debug, BinRead, BinWrite)]
+pub struct VtPackRawFile {
+    pub header: VtPackRawHeader,
+
+    // Writing mirrors this, leaving any gap between the header and the string table untouched
+    #[brw(seek_before = SeekFrom::Start(header.get_str_table_abs_offset()))]
     pub str_table: VtPackStringTable,
 
-    #[br(count = entry_count)]
+    #[br(count = header.entry_count)]
     pub entries: Vec<VtPackRawEntryHeader>
 }
 
+#[derive(Clone, Debug, Default)]
+pub struct VtPackReadOptions {
+    pub strict: bool,
+    pub raw_data: bool
+}
+
+impl VtPackReadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Refuse unknown versions instead of parsing them with the closest known layout
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    // Read every entry as stored, without detecting or decompressing any compression
+    pub fn raw_data(mut self, raw_data: bool) -> Self {
+        self.raw_data = raw_data;
+        self
+    }
+}
+
 pub struct VtPackFile {
     raw: VtPackRawFile,
-    p_entries: Vec<VtPackProcessedEntry>
+    p_entries: Vec<VtPackProcessedEntry>,
+    path_index: HashMap<String, usize>,
+    path_index_ignore_case: HashMap<String, usize>,
+    endian: Endian,
+    path_separator: char,
+    timestamp_decoder: Option<VtPackTimestampDecoder>,
+    raw_data: bool
+}
+
+fn read_table_string(table_data: &[u8], offset: u32) -> Result<String> {
+    if offset == INVALID_STRING_TABLE_OFFSET {
+        return Ok(String::new());
+    }
+
+    let str_ref = table_data.get(offset as usize..).ok_or(VtPackError::InvalidStringOffset(offset))?;
+    let c_str = CStr::from_bytes_until_nul(str_ref).map_err(|_| VtPackError::UnterminatedString(offset))?;
+    Ok(c_str.to_string_lossy().to_string())
 }
 
 impl VtPackFile {
-    fn process_entries(&mut self) {
+    fn process_entries(&mut self) -> Result<()> {
         self.p_entries.clear();
+        self.path_index.clear();
+        self.path_index_ignore_case.clear();
 
         for entry in self.raw.entries.iter() {
-            let dir_str = if entry.path_dir_str_table_offset != INVALID_STRING_TABLE_OFFSET {
-                let str_ref = &self.raw.str_table.table_data[entry.path_dir_str_table_offset as usize..];
-                CStr::from_bytes_until_nul(str_ref).unwrap().to_string_lossy().to_string()
-            }
-            else {
-                String::new()
-            };
-
-            let name_str = if entry.path_name_str_table_offset != INVALID_STRING_TABLE_OFFSET {
-                let str_ref = &self.raw.str_table.table_data[entry.path_name_str_table_offset as usize..];
-                CStr::from_bytes_until_nul(str_ref).unwrap().to_string_lossy().to_string()
-            }
-            else {
-                String::new()
-            };
+            let dir_str = read_table_string(&self.raw.str_table.table_data, entry.path_dir_str_table_offset)?;
+            let name_str = read_table_string(&self.raw.str_table.table_data, entry.path_name_str_table_offset)?;
 
             // TODO: easier way to ensure Rust doesn't treat these raw paths as absolute (they all start with "\")
-            let mut path = format!("{}\\{}", dir_str, name_str).replace("\\\\", "\\").replace("\\", std::path::MAIN_SEPARATOR_STR);
+            let separator = self.path_separator.to_string();
+            let mut path = format!("{}{}{}", dir_str, separator, name_str).replace(&separator.repeat(2), &separator).replace(&separator, std::path::MAIN_SEPARATOR_STR);
             while path.starts_with(std::path::MAIN_SEPARATOR) {
                 path.remove(0);
             }
 
+            // In case of duplicate paths, the first entry wins
+            let entry_idx = self.p_entries.len();
+            self.path_index.entry(make_lookup_key(&path, false)).or_insert(entry_idx);
+            self.path_index_ignore_case.entry(make_lookup_key(&path, true)).or_insert(entry_idx);
+
             let p_entry = VtPackProcessedEntry {
                 is_file: entry.file_data_abs_offset != 0,
                 path,
+                dir_str,
+                name_str,
                 file_size: entry.file_size as usize,
-                file_data_abs_offset: entry.file_data_abs_offset
+                file_data_abs_offset: entry.file_data_abs_offset,
+                modified_time: self.timestamp_decoder.and_then(|decoder| decoder.decode(entry)),
+                compressed_size_pair: get_compressed_size_pair(entry.file_size, entry.unk2)
             };
             self.p_entries.push(p_entry);
         }
+
+        Ok(())
     }
 
-    pub fn new<R: Seek + Read>(reader: &mut R) -> BinResult<Self> {
-        let raw = VtPackRawFile::read(reader)?;
+    pub fn new<R: Seek + Read>(reader: &mut R) -> Result<Self> {
+        Self::new_with_options(reader, &VtPackReadOptions::default())
+    }
+
+    pub fn new_with_options<R: Seek + Read>(reader: &mut R, options: &VtPackReadOptions) -> Result<Self> {
+        let endian = VtPackRawHeader::detect_endian(reader)?;
+        if options.strict {
+            let start_pos = reader.stream_position()?;
+            let version = VtPackRawHeader::read_options(reader, endian, ())?.version;
+            if !version.is_known() {
+                return Err(VtPackError::UnknownVersion(version.get_raw()));
+            }
+            reader.seek(SeekFrom::Start(start_pos))?;
+        }
+
+        let raw = VtPackRawFile::read_options(reader, endian, ())?;
 
         let mut file = Self {
             raw,
-            p_entries: Vec::new()
+            p_entries: Vec::new(),
+            path_index: HashMap::new(),
+            path_index_ignore_case: HashMap::new(),
+            endian,
+            path_separator: '\\',
+            timestamp_decoder: None,
+            raw_data: options.raw_data
         };
-        file.process_entries();
+        file.process_entries()?;
         Ok(file)
     }
 
+    pub fn get_raw(&self) -> &VtPackRawFile {
+        &self.raw
+    }
+
+    pub fn get_endian(&self) -> Endian {
+        self.endian
+    }
+
+    // Writes the header, string table and entry headers back at their original (absolute) offsets, in the original byte order
+    pub fn write_metadata<W: Write + Seek>(&self, writer: &mut W) -> Result<()> {
+        writer.rewind()?;
+        self.raw.write_options(writer, self.endian, ())?;
+        Ok(())
+    }
+
     pub fn list_entries(&self) -> &Vec<VtPackProcessedEntry> {
         &self.p_entries
     }
 
-    pub fn from_file(f: &File) -> BinResult<Self> {
+    pub fn find_index<S: AsRef<str>>(&self, path: S, ignore_case: bool) -> Option<usize> {
+        let key = make_lookup_key(path.as_ref(), ignore_case);
+        if ignore_case {
+            self.path_index_ignore_case.get(&key).copied()
+        }
+        else {
+            self.path_index.get(&key).copied()
+        }
+    }
+
+    pub fn find<S: AsRef<str>>(&self, path: S) -> Option<&VtPackProcessedEntry> {
+        self.find_index(path, false).map(|idx| &self.p_entries[idx])
+    }
+
+    pub fn find_ignore_case<S: AsRef<str>>(&self, path: S) -> Option<&VtPackProcessedEntry> {
+        self.find_index(path, true).map(|idx| &self.p_entries[idx])
+    }
+
+    pub fn filter_entries<'a>(&'a self, filter: &'a VtPackEntryFilter) -> impl Iterator<Item = &'a VtPackProcessedEntry> + 'a {
+        self.p_entries.iter().filter(move |entry| filter.matches(entry))
+    }
+
+    pub fn build_tree(&self) -> VtPackTree {
+        VtPackTree::new(self)
+    }
+
+    pub fn from_file(f: &File) -> Result<Self> {
         let mut br = BufReader::new(f);
         Self::new(&mut br)
     }
 
-    pub fn save_entry<R: Seek + Read, P: AsRef<Path>>(&self, reader: &mut R, entry: &VtPackProcessedEntry, out_path: P) {
-        let full_path = out_path.as_ref().join(entry.path.clone());
+    pub fn save_entry<R: Seek + Read, P: AsRef<Path>>(&self, reader: &mut R, entry: &VtPackProcessedEntry, out_path: P) -> Result<()> {
+        let full_path = out_path.as_ref().join(entry.get_safe_path()?);
 
         if entry.is_file {
-            let dir_path = full_path.parent().unwrap();
-            let _ = std::fs::create_dir_all(dir_path);
+            if let Some(dir_path) = full_path.parent() {
+                std::fs::create_dir_all(dir_path).with_path(dir_path)?;
+            }
+            self.write_entry_file(reader, entry, &full_path)?;
+        }
+        else {
+            std::fs::create_dir_all(&full_path).with_path(&full_path)?;
+        }
 
-            let mut file_data: Vec<u8> = vec![0; entry.file_size as usize];
-            reader.seek(SeekFrom::Start(entry.file_data_abs_offset)).unwrap();
-            reader.read(&mut file_data).unwrap();
+        Ok(())
+    }
 
-            let mut out_file_f = OpenOptions::new().create(true).write(true).truncate(true).open(full_path).unwrap();
-            out_file_f.write(&file_data).unwrap();
+    // The parent directory must already exist
+    pub(crate) fn write_entry_file<R: Seek + Read>(&self, reader: &mut R, entry: &VtPackProcessedEntry, full_path: &Path) -> Result<()> {
+        let mut entry_reader = self.open_entry(reader, entry)?;
+        let size = entry_reader.get_size();
+        Self::write_entry_data(entry, &mut entry_reader, full_path, size)
+    }
+
+    // Dry runs don't read the data, but still catch entries that are out of bounds or can't be decompressed
+    pub(crate) fn check_entry_file<R: Seek + Read>(&self, reader: &mut R, entry: &VtPackProcessedEntry) -> Result<()> {
+        self.open_entry(reader, entry)?;
+        Ok(())
+    }
+
+    fn check_entry_size(entry: &VtPackProcessedEntry, read_size: u64, size: u64) -> Result<()> {
+        if read_size != size {
+            return Err(VtPackError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, format!("entry '{}' data was cut short", entry.path))));
         }
-        else {
-            let _ = std::fs::create_dir_all(full_path);
+        Ok(())
+    }
+
+    // Streams have to read through the data anyway, so they can check all of it
+    pub(crate) fn check_entry_data<R: Read + ?Sized>(entry: &VtPackProcessedEntry, data_reader: &mut R, size: u64) -> Result<()> {
+        let read_size = io::copy(data_reader, &mut io::sink())?;
+        Self::check_entry_size(entry, read_size, size)
+    }
+
+    // Writes everything left in data_reader, which must be exactly size bytes
+    pub(crate) fn write_entry_data<R: Read + ?Sized>(entry: &VtPackProcessedEntry, data_reader: &mut R, full_path: &Path, size: u64) -> Result<()> {
+        let out_file_f = OpenOptions::new().create(true).write(true).truncate(true).open(full_path).with_path(full_path)?;
+        let mut out_writer = BufWriter::with_capacity(COPY_CHUNK_SIZE, out_file_f);
+
+        let mut chunk = vec![0; COPY_CHUNK_SIZE];
+        let mut read_size = 0;
+        loop {
+            let read_len = match data_reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read_len) => read_len,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into())
+            };
+            out_writer.write_all(&chunk[..read_len]).with_path(full_path)?;
+            read_size += read_len as u64;
+        }
+        out_writer.flush().with_path(full_path)?;
+        if let Some(modified_time) = entry.modified_time {
+            out_writer.get_ref().set_modified(modified_time).with_path(full_path)?;
+        }
+
+        Self::check_entry_size(entry, read_size, size)
+    }
+
+    pub fn export_filtered<R: Seek + Read, P: AsRef<Path>>(&self, reader: &mut R, out_path: P, filter: &VtPackEntryFilter) -> Result<VtPackExtractReport> {
+        self.export(reader, out_path, filter, &VtPackExtractOptions::default())
+    }
+
+    pub fn export_all<R: Seek + Read, P: AsRef<Path>>(&self, reader: &mut R, out_path: P) -> Result<VtPackExtractReport> {
+        self.export(reader, out_path, &VtPackEntryFilter::new(), &VtPackExtractOptions::default())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{io::Cursor, collections::BTreeMap};
+    use super::*;
+
+    const TEST_STR_TABLE_ABS_OFFSET: u64 = 0x40;
+    const TEST_ENTRIES_END: u64 = TEST_STR_TABLE_ABS_OFFSET + 4 + 25 + 3 * RAW_ENTRY_HEADER_SIZE;
+
+    fn make_test_entry(name_offset: u32, dir_offset: u32, file_size: u64, file_data_abs_offset: u64) -> VtPackRawEntryHeader {
+        VtPackRawEntryHeader {
+            path_name_str_table_offset: name_offset,
+            path_dir_str_table_offset: dir_offset,
+            unk1: 0x01020304,
+            file_size,
+            unk2: file_size,
+            file_data_abs_offset,
+            unk3: 0x05060708,
+            unk4: 0x090A0B0C
+        }
+    }
+
+    // Leaves a gap after the header, has an unreferenced string, and stores the data of the second file before the first one, aligned to 0x10 with unreferenced bytes in between
+    pub(crate) fn make_test_archive(version: VtPackVersion, endian: Endian) -> Vec<u8> {
+        let mut header = VtPackRawHeader::new(version, 3, TEST_STR_TABLE_ABS_OFFSET);
+        header.unk1 = 0x11223344;
+        header.unk2 = 0x55667788;
+        header.unk3_v1 = 0x99AABBCC;
+        header.unk3_v2 = 0x0102030405060708;
+        header.unk4_v1 = 0xDDEEFF00;
+        header.unk4_v2 = 0x1112131415161718;
+
+        let table_data = b"\0data\0a.bin\0unused\0b.bin\0".to_vec();
+        let raw = VtPackRawFile {
+            header,
+            str_table: VtPackStringTable {
+                table_size: table_data.len() as u32,
+                table_data
+            },
+            entries: vec![
+                make_test_entry(1, 0, 0, 0),
+                make_test_entry(6, 1, 7, 0x120),
+                make_test_entry(19, 1, 5, 0x100)
+            ]
+        };
+
+        let mut archive = vec![0; 0x130];
+        archive[raw.header.get_size() as usize..TEST_STR_TABLE_ABS_OFFSET as usize].fill(0xCD);
+        archive[0x100..0x105].copy_from_slice(b"bbbbb");
+        archive[0x108..0x110].fill(0xEE);
+        archive[0x120..0x127].copy_from_slice(b"aaaaaaa");
+        raw.write_options(&mut Cursor::new(&mut archive), endian, ()).unwrap();
+        archive
+    }
+
+    // Built as a v2 archive, then every file entry gets the given unk2 (entries are (path, stored data, unk2))
+    pub(crate) fn make_built_test_archive(entries: &[(&str, Vec<u8>, u64)]) -> Vec<u8> {
+        let mut builder = VtPackBuilder::new(VtPackVersion::Ver2);
+        for (path, data, _) in entries {
+            builder.add_file(path, VtPackDataSource::Bytes(data.clone())).unwrap();
+        }
+        let mut archive = Cursor::new(Vec::new());
+        builder.write(&mut archive).unwrap();
+
+        archive.set_position(0);
+        let file = VtPackFile::new(&mut archive).unwrap();
+        let mut raw = file.get_raw().clone();
+        for (raw_entry, entry) in raw.entries.iter_mut().zip(file.list_entries()) {
+            if let Some((_, _, unk2)) = entries.iter().find(|(path, _, _)| path == entry.get_path()) {
+                raw_entry.unk2 = *unk2;
+            }
         }
+        archive.set_position(0);
+        raw.write_options(&mut archive, file.get_endian(), ()).unwrap();
+        archive.into_inner()
+    }
+
+    pub(crate) fn make_test_out_dir(name: &str) -> PathBuf {
+        let out_path = std::env::temp_dir().join(format!("vtpack-{}-test-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&out_path);
+        out_path
     }
 
-    pub fn export_all<R: Seek + Read, P: AsRef<Path> + Clone>(&self, reader: &mut R, out_path: P) {
-        let _ = std::fs::remove_dir_all(out_path.as_ref());
-        let _ = std::fs::create_dir(out_path.as_ref());
+    // Maps every path below the directory to its file data, or to None for directories
+    pub(crate) fn read_test_tree(path: &Path) -> BTreeMap<PathBuf, Option<Vec<u8>>> {
+        let mut tree = BTreeMap::new();
+        let mut pending_dirs = vec![path.to_path_buf()];
+        while let Some(dir_path) = pending_dirs.pop() {
+            for dir_entry in std::fs::read_dir(&dir_path).unwrap() {
+                let entry_path = dir_entry.unwrap().path();
+                let rel_path = entry_path.strip_prefix(path).unwrap().to_path_buf();
+                if entry_path.is_dir() {
+                    tree.insert(rel_path, None);
+                    pending_dirs.push(entry_path);
+                }
+                else {
+                    tree.insert(rel_path, Some(std::fs::read(&entry_path).unwrap()));
+                }
+            }
+        }
+        tree
+    }
 
-        for p_entry in self.p_entries.iter() {
-            self.save_entry(reader, p_entry, out_path.clone());
+    pub(crate) fn list_test_report(report: &VtPackExtractReport, out_path: &Path) -> Vec<(usize, PathBuf, VtPackExtractAction)> {
+        report.get_entries().iter().map(|extracted_entry| (extracted_entry.get_entry_index(), extracted_entry.get_out_path().strip_prefix(out_path).unwrap().to_path_buf(), extracted_entry.get_action())).collect()
+    }
+
+    #[test]
+    fn write_metadata_round_trip() {
+        for version in [VtPackVersion::Ver1, VtPackVersion::Ver2] {
+            for endian in [Endian::Little, Endian::Big] {
+                let archive = make_test_archive(version, endian);
+                let file = VtPackFile::new(&mut Cursor::new(&archive)).unwrap();
+                assert_eq!(file.get_endian(), endian);
+                assert_eq!(file.list_entries().len(), 3);
+
+                // Only the metadata gets written, everything else must stay untouched
+                let mut written = vec![0xAA; archive.len()];
+                file.write_metadata(&mut Cursor::new(&mut written)).unwrap();
+
+                let mut expected = vec![0xAA; archive.len()];
+                let header_size = get_raw_header_size(version) as usize;
+                expected[..header_size].copy_from_slice(&archive[..header_size]);
+                let metadata_range = TEST_STR_TABLE_ABS_OFFSET as usize..TEST_ENTRIES_END as usize;
+                expected[metadata_range.clone()].copy_from_slice(&archive[metadata_range]);
+                assert_eq!(written, expected, "v{} {:?}", version, endian);
+            }
         }
     }
 }
@@ -0,0 +1,107 @@
+use std::{borrow::Cow, fs::File, io::{Cursor, Read}, path::Path};
+use memmap2::Mmap;
+use crate::{VtPackFile, VtPackProcessedEntry, VtPackEntryFilter, VtPackExtractOptions, VtPackExtractReport, VtPackReadOptions, VtPackError, Result, IoResultExt};
+
+// Entry data is handed out as slices of the mapping, without any copies
+pub struct VtPackMmapArchive {
+    mmap: Mmap,
+    file: VtPackFile,
+    ignore_case: bool
+}
+
+impl VtPackMmapArchive {
+    pub fn new(mmap: Mmap) -> Result<Self> {
+        Self::new_with_options(mmap, &VtPackReadOptions::default())
+    }
+
+    pub fn new_with_options(mmap: Mmap, options: &VtPackReadOptions) -> Result<Self> {
+        let file = VtPackFile::new_with_options(&mut Cursor::new(&mmap[..]), options)?;
+        Ok(Self {
+            mmap,
+            file,
+            ignore_case: false
+        })
+    }
+
+    // The archive must not be modified by anyone else while it's mapped, or the slices would change under our feet
+    pub fn open_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let f = File::open(path).with_path(path)?;
+        let mmap = unsafe { Mmap::map(&f) }.with_path(path)?;
+        Self::new(mmap)
+    }
+
+    pub fn get_file(&self) -> &VtPackFile {
+        &self.file
+    }
+
+    pub fn get_file_mut(&mut self) -> &mut VtPackFile {
+        &mut self.file
+    }
+
+    pub fn get_bytes(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    pub fn entries(&self) -> &Vec<VtPackProcessedEntry> {
+        self.file.list_entries()
+    }
+
+    pub fn set_ignore_case(&mut self, ignore_case: bool) {
+        self.ignore_case = ignore_case;
+    }
+
+    fn find_entry_index(&self, path: &str) -> Result<usize> {
+        self.file.find_index(path, self.ignore_case).ok_or_else(|| VtPackError::EntryNotFound(path.to_string()))
+    }
+
+    pub fn find<S: AsRef<str>>(&self, path: S) -> Option<&VtPackProcessedEntry> {
+        self.file.find_index(path, self.ignore_case).map(|entry_idx| &self.file.list_entries()[entry_idx])
+    }
+
+    // The data exactly as stored: compressed entries need to go through open() instead
+    pub fn get_entry_data(&self, entry: &VtPackProcessedEntry) -> Result<&[u8]> {
+        let (stored_size, _) = self.file.get_stored_data(&mut self.make_reader(), entry)?;
+        let start = entry.get_file_data_abs_offset() as usize;
+        Ok(&self.mmap[start..start + stored_size as usize])
+    }
+
+    pub fn get_data<S: AsRef<str>>(&self, path: S) -> Result<&[u8]> {
+        let entry_idx = self.find_entry_index(path.as_ref())?;
+        self.get_entry_data(&self.file.list_entries()[entry_idx])
+    }
+
+    // Every file entry along with its data
+    pub fn file_data(&self) -> impl Iterator<Item = (&VtPackProcessedEntry, Result<&[u8]>)> + '_ {
+        self.entries().iter().filter(|entry| entry.is_file()).map(move |entry| (entry, self.get_entry_data(entry)))
+    }
+
+    // Gives access to everything taking a reader, reading straight from the mapping
+    pub fn make_reader(&self) -> Cursor<&[u8]> {
+        Cursor::new(&self.mmap[..])
+    }
+
+    // Only compressed entries get copied, to decompress them
+    pub fn read<S: AsRef<str>>(&self, path: S) -> Result<Cow<'_, [u8]>> {
+        let entry = &self.file.list_entries()[self.find_entry_index(path.as_ref())?];
+        let mut reader = self.make_reader();
+        let (stored_size, info) = self.file.get_stored_data(&mut reader, entry)?;
+        if info.is_none() {
+            let start = entry.get_file_data_abs_offset() as usize;
+            return Ok(Cow::Borrowed(&self.mmap[start..start + stored_size as usize]));
+        }
+
+        let mut entry_reader = self.file.open_entry(&mut reader, entry)?;
+        let mut data = Vec::with_capacity(entry_reader.get_size() as usize);
+        entry_reader.read_to_end(&mut data)?;
+        Ok(Cow::Owned(data))
+    }
+
+    pub fn extract_with_options<P: AsRef<Path>>(&self, out_path: P, filter: &VtPackEntryFilter, options: &VtPackExtractOptions) -> Result<VtPackExtractReport> {
+        self.file.export(&mut self.make_reader(), out_path, filter, options)
+    }
+
+    pub fn extract_to<P: AsRef<Path>>(&self, out_path: P) -> Result<VtPackExtractReport> {
+        self.extract_with_options(out_path, &VtPackEntryFilter::new(), &VtPackExtractOptions::default())
+    }
+}
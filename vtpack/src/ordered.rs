@@ -0,0 +1,193 @@
+use std::{io::{self, Read, Seek, SeekFrom}, path::Path};
+use crate::{VtPackFile, VtPackArchive, VtPackEntryFilter, VtPackExtractOptions, VtPackExtractReport, Result, COPY_CHUNK_SIZE, reader::resolve_seek};
+
+// Entries close enough to each other are read together, up to this much data at once
+const MAX_WINDOW_SIZE: u64 = 8 * COPY_CHUNK_SIZE as u64;
+// Unreferenced data in between is read and thrown away instead of seeking over it
+const MAX_WINDOW_GAP: u64 = 0x10000;
+
+// A buffered range of the archive, still addressed by absolute offsets so that entries can be opened from it as usual
+struct VtPackWindowReader<'a> {
+    data: &'a [u8],
+    base_offset: u64,
+    archive_size: u64,
+    pos: u64
+}
+
+impl Read for VtPackWindowReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let window_end = self.base_offset + self.data.len() as u64;
+        if self.pos < self.base_offset || self.pos > window_end {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("read at {:#X} outside of the buffered window", self.pos)));
+        }
+
+        let start = (self.pos - self.base_offset) as usize;
+        let read_len = buf.len().min(self.data.len() - start);
+        buf[..read_len].copy_from_slice(&self.data[start..start + read_len]);
+        self.pos += read_len as u64;
+        Ok(read_len)
+    }
+}
+
+impl Seek for VtPackWindowReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = resolve_seek(pos, self.pos, self.archive_size)?;
+        Ok(self.pos)
+    }
+}
+
+impl VtPackFile {
+    // Same as export, but file data is read in a single forward pass over the archive, in offset order
+    // Meant for slow seeking storage like hard drives or network mounts
+    pub fn export_ordered<R: Seek + Read, P: AsRef<Path>>(&self, reader: &mut R, out_path: P, filter: &VtPackEntryFilter, options: &VtPackExtractOptions) -> Result<VtPackExtractReport> {
+        let report = self.prepare_export(out_path.as_ref(), filter, options)?;
+        let archive_size = reader.seek(SeekFrom::End(0))?;
+        // Compressed entries store less than their file size, so windows are planned from it and compressions are detected on the buffered data
+        // Out of bounds data is left out of the window, opening the entry reports it as usual
+        let mut jobs: Vec<_> = report.entries.iter()
+            .filter(|extracted_entry| extracted_entry.writes_file())
+            .map(|extracted_entry| {
+                let entry = &self.p_entries[extracted_entry.entry_index];
+                let start = entry.file_data_abs_offset.min(archive_size);
+                let end = entry.file_data_abs_offset.saturating_add(entry.file_size as u64).min(archive_size);
+                (start, end, extracted_entry)
+            })
+            .collect();
+        jobs.sort_by_key(|&(start, end, extracted_entry)| (start, end, extracted_entry.entry_index));
+
+        let mut window = Vec::new();
+        let mut job_idx = 0;
+        while job_idx < jobs.len() {
+            let (window_start, mut window_end, _) = jobs[job_idx];
+            let mut window_job_count = 1;
+            while let Some(&(start, end, _)) = jobs.get(job_idx + window_job_count) {
+                if start > window_end + MAX_WINDOW_GAP || end.max(window_end) - window_start > MAX_WINDOW_SIZE {
+                    break;
+                }
+                window_end = window_end.max(end);
+                window_job_count += 1;
+            }
+            let window_jobs = &jobs[job_idx..job_idx + window_job_count];
+            job_idx += window_job_count;
+
+            // Too big to buffer, so it's streamed straight from the archive
+            if window_end - window_start > MAX_WINDOW_SIZE {
+                let (_, _, extracted_entry) = window_jobs[0];
+                let entry = &self.p_entries[extracted_entry.entry_index];
+                if options.dry_run {
+                    self.check_entry_file(reader, entry)?;
+                }
+                else {
+                    self.write_entry_file(reader, entry, &extracted_entry.out_path)?;
+                }
+                continue;
+            }
+
+            window.resize((window_end - window_start) as usize, 0);
+            reader.seek(SeekFrom::Start(window_start))?;
+            reader.read_exact(&mut window)?;
+
+            let mut window_reader = VtPackWindowReader {
+                data: &window,
+                base_offset: window_start,
+                archive_size,
+                pos: window_start
+            };
+            for &(_, _, extracted_entry) in window_jobs {
+                let entry = &self.p_entries[extracted_entry.entry_index];
+                if options.dry_run {
+                    self.check_entry_file(&mut window_reader, entry)?;
+                }
+                else {
+                    self.write_entry_file(&mut window_reader, entry, &extracted_entry.out_path)?;
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+impl<R: Read + Seek> VtPackArchive<R> {
+    pub fn extract_ordered<P: AsRef<Path>>(&mut self, out_path: P, filter: &VtPackEntryFilter, options: &VtPackExtractOptions) -> Result<VtPackExtractReport> {
+        let (file, reader) = self.get_file_and_reader();
+        file.export_ordered(reader, out_path, filter, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+    use binrw::Endian;
+    use flate2::{Compression, write::ZlibEncoder};
+    use super::*;
+    use crate::{VtPackVersion, VtPackError, tests::{make_test_archive, make_built_test_archive, make_test_out_dir, read_test_tree, list_test_report}};
+
+    fn check_ordered_export(archive: &[u8], name: &str) {
+        let out_path = make_test_out_dir(name);
+        let ordered_out_path = make_test_out_dir(&format!("{}-ordered", name));
+        let file = VtPackFile::new(&mut Cursor::new(archive)).unwrap();
+        for dry_run in [false, true] {
+            let options = VtPackExtractOptions::new().clean_target(true).dry_run(dry_run);
+            let report = file.export(&mut Cursor::new(archive), &out_path, &VtPackEntryFilter::new(), &options).unwrap();
+            let ordered_report = file.export_ordered(&mut Cursor::new(archive), &ordered_out_path, &VtPackEntryFilter::new(), &options).unwrap();
+            assert_eq!(list_test_report(&ordered_report, &ordered_out_path), list_test_report(&report, &out_path));
+            if !dry_run {
+                assert_eq!(read_test_tree(&ordered_out_path), read_test_tree(&out_path));
+            }
+        }
+        std::fs::remove_dir_all(&out_path).unwrap();
+        std::fs::remove_dir_all(&ordered_out_path).unwrap();
+    }
+
+    #[test]
+    fn ordered_matches_export() {
+        for version in [VtPackVersion::Ver1, VtPackVersion::Ver2] {
+            for endian in [Endian::Little, Endian::Big] {
+                check_ordered_export(&make_test_archive(version, endian), "ordered");
+            }
+        }
+    }
+
+    #[test]
+    fn ordered_splits_windows() {
+        let window_size = MAX_WINDOW_SIZE as usize;
+        let plain_data: Vec<u8> = (0..window_size / 2).map(|i| (i % 251) as u8).collect();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&plain_data).unwrap();
+        let zlib_data = encoder.finish().unwrap();
+
+        // The first two files fill a window, the big one is streamed on its own and the compressed one only buffers its stored data
+        let archive = make_built_test_archive(&[
+            ("part\\first.bin", vec![1; window_size / 2], 0),
+            ("part\\second.bin", vec![2; window_size / 2], 0),
+            ("part\\third.bin", vec![3; 100], 0),
+            ("big.bin", vec![4; window_size + 1], 0),
+            ("compressed.bin", zlib_data, plain_data.len() as u64),
+            ("empty.bin", Vec::new(), 0)
+        ]);
+        let file = VtPackFile::new(&mut Cursor::new(&archive)).unwrap();
+        assert!(file.open_entry(&mut Cursor::new(&archive), file.find("compressed.bin").unwrap()).unwrap().is_compressed());
+        check_ordered_export(&archive, "ordered-windows");
+    }
+
+    #[test]
+    fn ordered_reports_out_of_bounds_entries() {
+        // Cuts a.bin short, then leaves it starting past the end
+        let mut archive = make_test_archive(VtPackVersion::Ver2, Endian::Little);
+        archive.truncate(0x124);
+        let file = VtPackFile::new(&mut Cursor::new(&archive)).unwrap();
+        let out_path = make_test_out_dir("ordered-bounds");
+        let options = VtPackExtractOptions::new().dry_run(true);
+        assert!(matches!(file.export_ordered(&mut Cursor::new(&archive), &out_path, &VtPackEntryFilter::new(), &options), Err(VtPackError::EntryOutOfBounds { .. })));
+
+        // Everything before the broken entry in the archive is still written
+        assert!(matches!(file.export_ordered(&mut Cursor::new(&archive), &out_path, &VtPackEntryFilter::new(), &VtPackExtractOptions::new()), Err(VtPackError::EntryOutOfBounds { .. })));
+        assert_eq!(std::fs::read(out_path.join("data").join("b.bin")).unwrap(), b"bbbbb");
+
+        archive.truncate(0x110);
+        let file = VtPackFile::new(&mut Cursor::new(&archive)).unwrap();
+        assert!(matches!(file.export_ordered(&mut Cursor::new(&archive), &out_path, &VtPackEntryFilter::new(), &VtPackExtractOptions::new()), Err(VtPackError::EntryOutOfBounds { .. })));
+        std::fs::remove_dir_all(&out_path).unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+use std::{fs::File, io::{BufReader, Read, Seek}, path::{Path, PathBuf}, sync::{Mutex, atomic::{AtomicUsize, Ordering}}};
+use crate::{VtPackFile, VtPackSharedArchive, VtPackEntryFilter, VtPackExtractOptions, VtPackExtractReport, VtPackExtractedEntry, VtPackFailedEntry, Result, IoResultExt};
+
+// Failed entries are left out of the report
+#[derive(Debug)]
+pub struct VtPackParallelExtractReport {
+    report: VtPackExtractReport,
+    failed: Vec<VtPackFailedEntry>
+}
+
+impl VtPackParallelExtractReport {
+    pub fn get_report(&self) -> &VtPackExtractReport {
+        &self.report
+    }
+
+    pub fn get_failed(&self) -> &Vec<VtPackFailedEntry> {
+        &self.failed
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+fn get_worker_count(options: &VtPackExtractOptions, job_count: usize) -> usize {
+    let worker_count = match options.worker_count {
+        0 => std::thread::available_parallelism().map(|count| count.get()).unwrap_or(1),
+        worker_count => worker_count
+    };
+    worker_count.min(job_count).max(1)
+}
+
+impl VtPackFile {
+    // Each worker gets its own reader from make_reader; a broken entry doesn't stop the others from being extracted
+    pub fn export_parallel<R: Read + Seek + Send, F: Fn() -> Result<R>, P: AsRef<Path>>(&self, make_reader: F, out_path: P, filter: &VtPackEntryFilter, options: &VtPackExtractOptions) -> Result<VtPackParallelExtractReport> {
+        let (mut report, mut failed) = self.plan_export(out_path.as_ref(), filter, options, true)?;
+        let jobs: Vec<&VtPackExtractedEntry> = report.entries.iter().filter(|extracted_entry| extracted_entry.writes_file()).collect();
+        let mut readers = Vec::new();
+        for _ in 0..get_worker_count(options, jobs.len()) {
+            readers.push(make_reader()?);
+        }
+
+        let next_job = AtomicUsize::new(0);
+        let worker_failed = Mutex::new(Vec::new());
+        std::thread::scope(|scope| {
+            for mut reader in readers {
+                let (jobs, next_job, worker_failed) = (&jobs, &next_job, &worker_failed);
+                scope.spawn(move || {
+                    while let Some(job) = jobs.get(next_job.fetch_add(1, Ordering::Relaxed)) {
+                        let entry = &self.p_entries[job.entry_index];
+                        let res = if options.dry_run {
+                            self.check_entry_file(&mut reader, entry)
+                        }
+                        else {
+                            self.write_entry_file(&mut reader, entry, &job.out_path)
+                        };
+
+                        if let Err(err) = res {
+                            worker_failed.lock().unwrap().push(VtPackFailedEntry {
+                                entry_index: job.entry_index,
+                                error: err
+                            });
+                        }
+                    }
+                });
+            }
+        });
+
+        failed.extend(worker_failed.into_inner().unwrap());
+        failed.sort_by_key(|failed_entry| failed_entry.entry_index);
+        report.entries.retain(|extracted_entry| failed.binary_search_by_key(&extracted_entry.entry_index, |failed_entry| failed_entry.entry_index).is_err());
+
+        Ok(VtPackParallelExtractReport {
+            report,
+            failed
+        })
+    }
+
+    // Every worker opens the archive on its own
+    pub fn export_parallel_from_path<P: AsRef<Path>, Q: AsRef<Path>>(&self, archive_path: P, out_path: Q, filter: &VtPackEntryFilter, options: &VtPackExtractOptions) -> Result<VtPackParallelExtractReport> {
+        let archive_path: PathBuf = archive_path.as_ref().to_path_buf();
+        self.export_parallel(|| Ok(BufReader::new(File::open(&archive_path).with_path(&archive_path)?)), out_path, filter, options)
+    }
+}
+
+impl VtPackSharedArchive {
+    pub fn extract_parallel<P: AsRef<Path>>(&self, out_path: P, filter: &VtPackEntryFilter, options: &VtPackExtractOptions) -> Result<VtPackParallelExtractReport> {
+        self.get_file().export_parallel(|| self.make_reader(), out_path, filter, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use binrw::Endian;
+    use super::*;
+    use crate::{VtPackVersion, VtPackError, VtPackExtractAction, VtPackOverwritePolicy, tests::{make_test_archive, make_test_out_dir, read_test_tree, list_test_report}};
+
+    #[test]
+    fn parallel_matches_export() {
+        let out_path = make_test_out_dir("parallel");
+        let parallel_out_path = make_test_out_dir("parallel-workers");
+        for version in [VtPackVersion::Ver1, VtPackVersion::Ver2] {
+            for endian in [Endian::Little, Endian::Big] {
+                for dry_run in [false, true] {
+                    let archive = make_test_archive(version, endian);
+                    let file = VtPackFile::new(&mut Cursor::new(&archive)).unwrap();
+                    let options = VtPackExtractOptions::new().clean_target(true).dry_run(dry_run).worker_count(2);
+
+                    let report = file.export(&mut Cursor::new(&archive), &out_path, &VtPackEntryFilter::new(), &options).unwrap();
+                    let parallel_report = file.export_parallel(|| Ok(Cursor::new(archive.as_slice())), &parallel_out_path, &VtPackEntryFilter::new(), &options).unwrap();
+                    assert!(parallel_report.is_complete());
+                    assert_eq!(list_test_report(parallel_report.get_report(), &parallel_out_path), list_test_report(&report, &out_path));
+                    assert_eq!(report.count_action(VtPackExtractAction::Write), 2);
+                    if !dry_run {
+                        assert_eq!(read_test_tree(&parallel_out_path), read_test_tree(&out_path));
+                    }
+                }
+            }
+        }
+        std::fs::remove_dir_all(&out_path).unwrap();
+        std::fs::remove_dir_all(&parallel_out_path).unwrap();
+    }
+
+    #[test]
+    fn collect_entry_failures() {
+        // Cuts the data of a.bin short, b.bin is stored before it
+        let mut archive = make_test_archive(VtPackVersion::Ver2, Endian::Little);
+        archive.truncate(0x124);
+        let file = VtPackFile::new(&mut Cursor::new(&archive)).unwrap();
+        let out_path = make_test_out_dir("parallel-failures");
+
+        for dry_run in [true, false] {
+            let options = VtPackExtractOptions::new().dry_run(dry_run);
+            assert!(matches!(file.export(&mut Cursor::new(&archive), &out_path, &VtPackEntryFilter::new(), &options), Err(VtPackError::EntryOutOfBounds { .. })));
+
+            let parallel_report = file.export_parallel(|| Ok(Cursor::new(archive.as_slice())), &out_path, &VtPackEntryFilter::new(), &options).unwrap();
+            let failed_indices: Vec<usize> = parallel_report.get_failed().iter().map(|failed_entry| failed_entry.get_entry_index()).collect();
+            assert_eq!(failed_indices, [1]);
+            assert!(matches!(parallel_report.get_failed()[0].get_error(), VtPackError::EntryOutOfBounds { .. }));
+            assert_eq!(list_test_report(parallel_report.get_report(), &out_path), [
+                (0, PathBuf::from("data"), VtPackExtractAction::CreateDir),
+                (2, PathBuf::from("data").join("b.bin"), VtPackExtractAction::Write)
+            ]);
+        }
+
+        assert_eq!(std::fs::read(out_path.join("data").join("b.bin")).unwrap(), b"bbbbb");
+        std::fs::remove_dir_all(&out_path).unwrap();
+    }
+
+    #[test]
+    fn conflicts_abort_before_writing() {
+        let archive = make_test_archive(VtPackVersion::Ver2, Endian::Little);
+        let file = VtPackFile::new(&mut Cursor::new(&archive)).unwrap();
+        let out_path = make_test_out_dir("parallel-conflicts");
+        std::fs::create_dir_all(out_path.join("data")).unwrap();
+        std::fs::write(out_path.join("data").join("b.bin"), b"existing").unwrap();
+
+        let options = VtPackExtractOptions::new().overwrite_policy(VtPackOverwritePolicy::FailOnConflict);
+        assert!(matches!(file.export_parallel(|| Ok(Cursor::new(archive.as_slice())), &out_path, &VtPackEntryFilter::new(), &options), Err(VtPackError::OutputExists(_))));
+        assert!(!out_path.join("data").join("a.bin").exists());
+        assert_eq!(std::fs::read(out_path.join("data").join("b.bin")).unwrap(), b"existing");
+        std::fs::remove_dir_all(&out_path).unwrap();
+    }
+}
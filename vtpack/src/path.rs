@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+use crate::{VtPackError, Result};
+
+pub(crate) fn split_path_components(path: &str) -> impl Iterator<Item = &str> {
+    path.split(['\\', '/']).filter(|comp| !comp.is_empty())
+}
+
+// Paths are matched the way the game's Windows file layer does: either separator works, leading/repeated separators are ignored and case optionally doesn't matter
+pub(crate) fn make_lookup_key(path: &str, ignore_case: bool) -> String {
+    let key = split_path_components(path).collect::<Vec<_>>().join("/");
+    if ignore_case {
+        key.to_lowercase()
+    }
+    else {
+        key
+    }
+}
+
+// Archive paths come from untrusted data: never let them escape the output directory
+pub(crate) fn sanitize_entry_path(path: &str) -> Result<PathBuf> {
+    let unsafe_path = |reason| Err(VtPackError::UnsafePath {
+        path: path.to_string(),
+        reason
+    });
+
+    if path.contains('\0') {
+        return unsafe_path("contains a NUL byte");
+    }
+
+    let mut safe_path = PathBuf::new();
+    for comp in split_path_components(path) {
+        match comp {
+            "." => continue,
+            ".." => return unsafe_path("contains a parent directory component"),
+            "?" => return unsafe_path("contains a verbatim path prefix"),
+            comp if comp.contains(':') => return unsafe_path("contains a drive prefix or stream separator"),
+            comp => safe_path.push(comp)
+        }
+    }
+    Ok(safe_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::*;
+
+    fn assert_unsafe(path: &str) {
+        assert!(matches!(sanitize_entry_path(path), Err(VtPackError::UnsafePath { .. })), "'{}' was not rejected", path.escape_debug());
+    }
+
+    fn assert_safe(path: &str, expected: &[&str]) {
+        let expected: PathBuf = expected.iter().collect();
+        assert_eq!(sanitize_entry_path(path).unwrap(), expected, "'{}'", path.escape_debug());
+    }
+
+    #[test]
+    fn sanitize_parent_components() {
+        assert_unsafe("..");
+        assert_unsafe("..\\x");
+        assert_unsafe("a/../../x");
+        assert_unsafe(".\\..\\x");
+        assert_unsafe("a\\.\\..\\..\\x");
+        assert_safe(".\\a\\.\\b", &["a", "b"]);
+    }
+
+    #[test]
+    fn sanitize_mixed_separators() {
+        assert_safe("\\data/sub\\file.bin", &["data", "sub", "file.bin"]);
+        assert_safe("//data\\\\sub//\\file.bin", &["data", "sub", "file.bin"]);
+        assert_unsafe("data/..\\..\\x");
+        assert_eq!(sanitize_entry_path("\\").unwrap(), Path::new(""));
+    }
+
+    #[test]
+    fn sanitize_drive_prefixes() {
+        assert_unsafe("C:foo");
+        assert_unsafe("C:\\foo");
+        assert_unsafe("c:/foo");
+        assert_unsafe("data\\C:foo");
+    }
+
+    #[test]
+    fn sanitize_device_prefixes() {
+        assert_unsafe("\\\\?\\C:\\x");
+        assert_unsafe("\\\\?\\UNC\\server\\share\\x");
+        assert_unsafe("//?/x");
+        // A \\.\ device prefix only adds a current directory component, which is dropped
+        assert_safe("\\\\.\\pipe\\x", &["pipe", "x"]);
+    }
+
+    #[test]
+    fn sanitize_alternate_data_streams() {
+        assert_unsafe("file.bin:stream");
+        assert_unsafe("data\\file.bin::$DATA");
+        assert_unsafe("data:stream\\file.bin");
+    }
+
+    #[test]
+    fn sanitize_nul_bytes() {
+        assert_unsafe("file.bin\0");
+        assert_unsafe("data\0\\file.bin");
+        assert_unsafe("\0");
+    }
+}
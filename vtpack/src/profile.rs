@@ -0,0 +1,177 @@
+use std::io::{Read, Seek};
+use binrw::{BinRead, Endian};
+use crate::{VtPackFile, VtPackRawHeader, VtPackVersion, VtPackTimestampDecoder, VtPackIntegrityCheck, VtPackBuilder, Result};
+
+pub type VtPackHeaderPredicate = Box<dyn Fn(&VtPackRawHeader) -> bool + Send + Sync>;
+
+// Everything that may differ between games using the format
+pub struct VtPackProfile {
+    name: String,
+    versions: Vec<VtPackVersion>,
+    endian: Endian,
+    path_separator: char,
+    ignore_case: bool,
+    timestamp_decoder: Option<VtPackTimestampDecoder>,
+    integrity_check: Option<VtPackIntegrityCheck>,
+    header_predicates: Vec<VtPackHeaderPredicate>
+}
+
+impl VtPackProfile {
+    pub fn new<S: AsRef<str>>(name: S) -> Self {
+        Self {
+            name: name.as_ref().to_string(),
+            versions: Vec::new(),
+            endian: Endian::Little,
+            path_separator: '\\',
+            ignore_case: false,
+            timestamp_decoder: None,
+            integrity_check: None,
+            header_predicates: Vec::new()
+        }
+    }
+
+    pub fn torrente3() -> Self {
+        Self::new("torrente3").version(VtPackVersion::Ver1).version(VtPackVersion::Ver2).ignore_case(true)
+    }
+
+    // No versions means any version is accepted; the first one is used when building
+    pub fn version(mut self, version: VtPackVersion) -> Self {
+        self.versions.push(version);
+        self
+    }
+
+    pub fn endian(mut self, endian: Endian) -> Self {
+        self.endian = endian;
+        self
+    }
+
+    pub fn path_separator(mut self, path_separator: char) -> Self {
+        self.path_separator = path_separator;
+        self
+    }
+
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    pub fn timestamp_decoder(mut self, decoder: VtPackTimestampDecoder) -> Self {
+        self.timestamp_decoder = Some(decoder);
+        self
+    }
+
+    pub fn integrity_check(mut self, check: VtPackIntegrityCheck) -> Self {
+        self.integrity_check = Some(check);
+        self
+    }
+
+    // Extra conditions on the header (like known unk values) to tell apart games sharing a version
+    pub fn detect_with<F: Fn(&VtPackRawHeader) -> bool + Send + Sync + 'static>(mut self, predicate: F) -> Self {
+        self.header_predicates.push(Box::new(predicate));
+        self
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_versions(&self) -> &Vec<VtPackVersion> {
+        &self.versions
+    }
+
+    pub fn get_endian(&self) -> Endian {
+        self.endian
+    }
+
+    pub fn get_path_separator(&self) -> char {
+        self.path_separator
+    }
+
+    pub fn get_ignore_case(&self) -> bool {
+        self.ignore_case
+    }
+
+    pub fn get_timestamp_decoder(&self) -> Option<VtPackTimestampDecoder> {
+        self.timestamp_decoder
+    }
+
+    pub fn get_integrity_check(&self) -> Option<VtPackIntegrityCheck> {
+        self.integrity_check
+    }
+
+    pub fn matches(&self, header: &VtPackRawHeader, endian: Endian) -> bool {
+        self.endian == endian
+            && (self.versions.is_empty() || self.versions.contains(&header.version))
+            && self.header_predicates.iter().all(|predicate| predicate(header))
+    }
+}
+
+pub struct VtPackProfileRegistry {
+    profiles: Vec<VtPackProfile>
+}
+
+impl Default for VtPackProfileRegistry {
+    fn default() -> Self {
+        Self {
+            profiles: vec![VtPackProfile::torrente3()]
+        }
+    }
+}
+
+impl VtPackProfileRegistry {
+    // Comes with the built-in profiles
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            profiles: Vec::new()
+        }
+    }
+
+    // Profiles registered later take precedence when detecting
+    pub fn register(&mut self, profile: VtPackProfile) {
+        self.profiles.push(profile);
+    }
+
+    pub fn get_profiles(&self) -> &Vec<VtPackProfile> {
+        &self.profiles
+    }
+
+    pub fn get<S: AsRef<str>>(&self, name: S) -> Option<&VtPackProfile> {
+        self.profiles.iter().rev().find(|profile| profile.name.eq_ignore_ascii_case(name.as_ref()))
+    }
+
+    pub fn detect(&self, header: &VtPackRawHeader, endian: Endian) -> Option<&VtPackProfile> {
+        self.profiles.iter().rev().find(|profile| profile.matches(header, endian))
+    }
+
+    // Leaves the reader back at the start
+    pub fn detect_from_reader<R: Seek + Read>(&self, reader: &mut R) -> Result<Option<&VtPackProfile>> {
+        reader.rewind()?;
+        let endian = VtPackRawHeader::detect_endian(reader)?;
+        let header = VtPackRawHeader::read_options(reader, endian, ())?;
+        reader.rewind()?;
+        Ok(self.detect(&header, endian))
+    }
+}
+
+impl VtPackFile {
+    pub fn apply_profile(&mut self, profile: &VtPackProfile) -> Result<()> {
+        self.path_separator = profile.path_separator;
+        self.process_entries()?;
+        self.set_timestamp_decoder(profile.timestamp_decoder);
+        Ok(())
+    }
+}
+
+impl VtPackBuilder {
+    pub fn from_profile(profile: &VtPackProfile) -> Self {
+        let mut builder = Self::new(profile.versions.first().copied().unwrap_or(VtPackVersion::Ver2));
+        builder.set_path_separator(profile.path_separator);
+        builder.set_endian(profile.endian);
+        builder.set_timestamp_field(profile.timestamp_decoder.map(|decoder| decoder.get_field()));
+        builder
+    }
+}
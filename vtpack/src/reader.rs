@@ -0,0 +1,192 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use crate::VtPackCompression;
+
+fn invalid_seek_error() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")
+}
+
+// New position for readers that keep their own position over something of a known size
+pub(crate) fn resolve_seek(pos: SeekFrom, cur: u64, size: u64) -> io::Result<u64> {
+    match pos {
+        SeekFrom::Start(offset) => Some(offset),
+        SeekFrom::End(offset) => size.checked_add_signed(offset),
+        SeekFrom::Current(offset) => cur.checked_add_signed(offset)
+    }.ok_or_else(invalid_seek_error)
+}
+
+// Reads the bytes stored in the archive for an entry, as they are
+pub struct VtPackRawEntryReader<'a, R: Read + Seek> {
+    reader: &'a mut R,
+    start: u64,
+    size: u64,
+    pos: u64
+}
+
+impl<'a, R: Read + Seek> VtPackRawEntryReader<'a, R> {
+    pub(crate) fn new(reader: &'a mut R, start: u64, size: u64) -> io::Result<Self> {
+        reader.seek(SeekFrom::Start(start))?;
+        Ok(Self {
+            reader,
+            start,
+            size,
+            pos: 0
+        })
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl<R: Read + Seek> Read for VtPackRawEntryReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.size.saturating_sub(self.pos);
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let max_len = buf.len().min(remaining.try_into().unwrap_or(usize::MAX));
+        let read_len = self.reader.read(&mut buf[..max_len])?;
+        self.pos += read_len as u64;
+        Ok(read_len)
+    }
+}
+
+impl<R: Read + Seek> Seek for VtPackRawEntryReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = resolve_seek(pos, self.pos, self.size)?;
+        let abs_pos = self.start.checked_add(new_pos).ok_or_else(invalid_seek_error)?;
+
+        self.reader.seek(SeekFrom::Start(abs_pos))?;
+        self.pos = new_pos;
+        Ok(new_pos)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.pos)
+    }
+}
+
+enum EntryData<'a, R: Read + Seek> {
+    Stored(VtPackRawEntryReader<'a, R>),
+    Zlib(ZlibDecoder<VtPackRawEntryReader<'a, R>>),
+    Deflate(DeflateDecoder<VtPackRawEntryReader<'a, R>>),
+    Gzip(GzDecoder<VtPackRawEntryReader<'a, R>>)
+}
+
+impl<'a, R: Read + Seek> EntryData<'a, R> {
+    fn new(raw_reader: VtPackRawEntryReader<'a, R>, compression: Option<VtPackCompression>) -> Self {
+        match compression {
+            Some(VtPackCompression::Zlib) => Self::Zlib(ZlibDecoder::new(raw_reader)),
+            Some(VtPackCompression::Deflate) => Self::Deflate(DeflateDecoder::new(raw_reader)),
+            Some(VtPackCompression::Gzip) => Self::Gzip(GzDecoder::new(raw_reader)),
+            // Compressions that can't be decoded are never detected for reading
+            _ => Self::Stored(raw_reader)
+        }
+    }
+
+    fn into_raw(self) -> VtPackRawEntryReader<'a, R> {
+        match self {
+            Self::Stored(raw_reader) => raw_reader,
+            Self::Zlib(decoder) => decoder.into_inner(),
+            Self::Deflate(decoder) => decoder.into_inner(),
+            Self::Gzip(decoder) => decoder.into_inner()
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Stored(raw_reader) => raw_reader.read(buf),
+            Self::Zlib(decoder) => decoder.read(buf),
+            Self::Deflate(decoder) => decoder.read(buf),
+            Self::Gzip(decoder) => decoder.read(buf)
+        }
+    }
+}
+
+// Reads the (decompressed, if needed) data of an entry
+pub struct VtPackEntryReader<'a, R: Read + Seek> {
+    // Only None while restarting decompression
+    data: Option<EntryData<'a, R>>,
+    compression: Option<VtPackCompression>,
+    size: u64,
+    pos: u64
+}
+
+impl<'a, R: Read + Seek> VtPackEntryReader<'a, R> {
+    pub(crate) fn new(raw_reader: VtPackRawEntryReader<'a, R>, compression: Option<VtPackCompression>, size: u64) -> Self {
+        Self {
+            data: Some(EntryData::new(raw_reader, compression)),
+            compression,
+            size,
+            pos: 0
+        }
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn get_compression(&self) -> Option<VtPackCompression> {
+        self.compression
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compression.is_some()
+    }
+
+    fn restart(&mut self) -> io::Result<()> {
+        let mut raw_reader = self.data.take().unwrap().into_raw();
+        let res = raw_reader.rewind();
+        self.data = Some(EntryData::new(raw_reader, self.compression));
+        self.pos = 0;
+        res
+    }
+}
+
+impl<R: Read + Seek> Read for VtPackEntryReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.size.saturating_sub(self.pos);
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let max_len = buf.len().min(remaining.try_into().unwrap_or(usize::MAX));
+        let read_len = self.data.as_mut().unwrap().read(&mut buf[..max_len])?;
+        self.pos += read_len as u64;
+        Ok(read_len)
+    }
+}
+
+impl<R: Read + Seek> Seek for VtPackEntryReader<'_, R> {
+    // Compressed data can't be seeked, so it's decompressed again from the start (or skipped forward)
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = resolve_seek(pos, self.pos, self.size)?;
+
+        match self.data.as_mut().unwrap() {
+            EntryData::Stored(raw_reader) => {
+                raw_reader.seek(SeekFrom::Start(new_pos))?;
+                self.pos = new_pos;
+            }
+            _ => {
+                if new_pos < self.pos {
+                    self.restart()?;
+                }
+                // Nothing is left to skip if a previous seek already went past the end
+                let skip_len = new_pos.min(self.size).saturating_sub(self.pos);
+                let skipped_len = io::copy(&mut self.by_ref().take(skip_len), &mut io::sink())?;
+                if skipped_len != skip_len {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "compressed entry data ended early"));
+                }
+                // Like regular files, seeking past the end is allowed
+                self.pos = new_pos;
+            }
+        }
+        Ok(new_pos)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.pos)
+    }
+}
@@ -0,0 +1,100 @@
+use std::{fs::File, io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write}, path::Path};
+use crate::{VtPackFile, VtPackError, Result, IoResultExt, RAW_ENTRY_HEADER_SIZE, COPY_CHUNK_SIZE};
+
+fn copy_range<R: Read + Seek, W: Write + Seek>(reader: &mut R, writer: &mut W, offset: u64, size: u64) -> Result<()> {
+    reader.seek(SeekFrom::Start(offset))?;
+    writer.seek(SeekFrom::Start(offset))?;
+    let copied_size = io::copy(&mut reader.by_ref().take(size), writer)?;
+    if copied_size != size {
+        return Err(VtPackError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, format!("archive data at {:#X} was cut short", offset))));
+    }
+    Ok(())
+}
+
+impl VtPackFile {
+    // Metadata comes from the parsed structures and file data from the entries, anything else (padding, unreferenced data) is copied over as-is
+    // Offsets are absolute, so the writer must start at the beginning of the output
+    pub fn repack<R: Read + Seek, W: Write + Seek>(&self, reader: &mut R, writer: &mut W) -> Result<()> {
+        let archive_size = reader.seek(SeekFrom::End(0))?;
+        let header = &self.raw.header;
+
+        let mut ranges = vec![
+            (0, header.get_size()),
+            (header.get_str_table_abs_offset(), header.get_entries_abs_offset(&self.raw.str_table) + self.raw.entries.len() as u64 * RAW_ENTRY_HEADER_SIZE)
+        ];
+        for entry in self.p_entries.iter().filter(|entry| entry.is_file()) {
+            // Also catches out of bounds entries, which can't be reproduced
+            let raw_reader = self.open_entry_raw(reader, entry)?;
+            ranges.push((entry.get_file_data_abs_offset(), entry.get_file_data_abs_offset() + raw_reader.get_size()));
+        }
+        ranges.sort();
+
+        let mut cur_offset = 0;
+        for (start, end) in ranges.iter().copied() {
+            if start > cur_offset {
+                copy_range(reader, writer, cur_offset, start - cur_offset)?;
+            }
+            cur_offset = cur_offset.max(end);
+        }
+        if archive_size > cur_offset {
+            copy_range(reader, writer, cur_offset, archive_size - cur_offset)?;
+        }
+
+        let mut chunk = vec![0; COPY_CHUNK_SIZE];
+        for entry in self.p_entries.iter().filter(|entry| entry.is_file()) {
+            let mut raw_reader = self.open_entry_raw(reader, entry)?;
+            writer.seek(SeekFrom::Start(entry.get_file_data_abs_offset()))?;
+            loop {
+                let read_len = match raw_reader.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(read_len) => read_len,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(err.into())
+                };
+                writer.write_all(&chunk[..read_len])?;
+            }
+        }
+
+        self.write_metadata(writer)?;
+        writer.seek(SeekFrom::Start(archive_size))?;
+        Ok(())
+    }
+
+    pub fn repack_to_file<R: Read + Seek, P: AsRef<Path>>(&self, reader: &mut R, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut writer = BufWriter::with_capacity(COPY_CHUNK_SIZE, File::create(path).with_path(path)?);
+        self.repack(reader, &mut writer)?;
+        writer.flush().with_path(path)?;
+        Ok(())
+    }
+
+    pub fn repack_file<P: AsRef<Path>, Q: AsRef<Path>>(in_path: P, out_path: Q) -> Result<()> {
+        let in_path = in_path.as_ref();
+        let mut reader = BufReader::new(File::open(in_path).with_path(in_path)?);
+        let file = Self::new(&mut reader)?;
+        file.repack_to_file(&mut reader, out_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use binrw::Endian;
+    use crate::{VtPackFile, VtPackVersion, tests::make_test_archive};
+
+    #[test]
+    fn repack_is_bit_for_bit() {
+        for version in [VtPackVersion::Ver1, VtPackVersion::Ver2] {
+            for endian in [Endian::Little, Endian::Big] {
+                let archive = make_test_archive(version, endian);
+                let mut reader = Cursor::new(&archive);
+                let file = VtPackFile::new(&mut reader).unwrap();
+
+                let mut writer = Cursor::new(Vec::new());
+                file.repack(&mut reader, &mut writer).unwrap();
+                assert_eq!(writer.position(), archive.len() as u64);
+                assert_eq!(writer.into_inner(), archive, "v{} {:?}", version, endian);
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, HashMap};
+use crate::{VtPackFile, VtPackVersion, is_plausible_filetime};
+
+const MOST_COMMON_VALUE_COUNT: usize = 5;
+
+#[derive(Clone, Debug)]
+pub struct VtPackFieldCorrelation {
+    pub with: &'static str,
+    // None if either side never changes
+    pub pearson: Option<f64>,
+    pub equal_fraction: f64
+}
+
+#[derive(Clone, Debug)]
+pub struct VtPackFieldReport {
+    pub name: String,
+    pub bits: u32,
+    pub sample_count: usize,
+    pub min: u64,
+    pub max: u64,
+    pub distinct_count: usize,
+    pub most_common: Vec<(u64, usize)>,
+    pub used_bits: u64,
+    pub correlations: Vec<VtPackFieldCorrelation>,
+    pub filetime_fraction: f64,
+    pub looks_like_filetime: bool,
+    pub looks_like_crc: bool,
+    pub looks_like_flags: bool
+}
+
+#[derive(Default)]
+struct FieldSamples {
+    bits: u32,
+    values: Vec<u64>,
+    correlates: Vec<(&'static str, Vec<u64>)>
+}
+
+impl FieldSamples {
+    fn push(&mut self, bits: u32, value: u64, correlates: &[(&'static str, u64)]) {
+        self.bits = self.bits.max(bits);
+        self.values.push(value);
+
+        if self.correlates.is_empty() {
+            self.correlates = correlates.iter().map(|(name, _)| (*name, Vec::new())).collect();
+        }
+        for ((_, samples), (_, correlate)) in self.correlates.iter_mut().zip(correlates) {
+            samples.push(*correlate);
+        }
+    }
+
+    fn make_report(&self, name: &str) -> VtPackFieldReport {
+        let sample_count = self.values.len();
+
+        let mut value_counts: HashMap<u64, usize> = HashMap::new();
+        for value in self.values.iter() {
+            *value_counts.entry(*value).or_insert(0) += 1;
+        }
+        let mut most_common: Vec<(u64, usize)> = value_counts.iter().map(|(value, count)| (*value, *count)).collect();
+        most_common.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        most_common.truncate(MOST_COMMON_VALUE_COUNT);
+
+        let correlations = self.correlates.iter().map(|(with, samples)| VtPackFieldCorrelation {
+            with,
+            pearson: pearson(&self.values, samples),
+            equal_fraction: fraction(self.values.iter().zip(samples).filter(|(value, sample)| value == sample).count(), sample_count)
+        }).collect::<Vec<_>>();
+
+        let nonzero_values: Vec<u64> = self.values.iter().copied().filter(|value| *value != 0).collect();
+        let used_bits = self.values.iter().fold(0, |bits, value| bits | value);
+        let distinct_count = value_counts.len();
+
+        let filetime_fraction = fraction(nonzero_values.iter().filter(|value| is_plausible_filetime(**value)).count(), nonzero_values.len());
+        let looks_like_filetime = self.bits == 64 && !nonzero_values.is_empty() && filetime_fraction >= 0.9;
+
+        // Checksums look like noise: (almost) all distinct, about half of the bits set, no relation to anything else
+        let avg_popcount = nonzero_values.iter().map(|value| value.count_ones() as f64).sum::<f64>() / nonzero_values.len().max(1) as f64;
+        // Small samples show some correlation just by chance
+        let max_noise_correlation = (3.0 / (sample_count.max(1) as f64).sqrt()).max(0.2);
+        let uncorrelated = correlations.iter().all(|correlation| correlation.pearson.is_none_or(|pearson| pearson.abs() < max_noise_correlation) && correlation.equal_fraction < 0.1);
+        let looks_like_crc = self.bits == 32 && sample_count >= 16 && fraction(distinct_count, sample_count) >= 0.9 && (avg_popcount - 16.0).abs() <= 2.0 && uncorrelated;
+
+        // Flag sets only use a few bits, with few bits set per value
+        let looks_like_flags = !nonzero_values.is_empty() && distinct_count <= 64 && used_bits.count_ones() <= 16 && avg_popcount <= 4.0 && !looks_like_filetime;
+
+        VtPackFieldReport {
+            name: name.to_string(),
+            bits: self.bits,
+            sample_count,
+            min: self.values.iter().copied().min().unwrap_or(0),
+            max: self.values.iter().copied().max().unwrap_or(0),
+            distinct_count,
+            most_common,
+            used_bits,
+            correlations,
+            filetime_fraction,
+            looks_like_filetime,
+            looks_like_crc,
+            looks_like_flags
+        }
+    }
+}
+
+fn fraction(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    }
+    else {
+        count as f64 / total as f64
+    }
+}
+
+fn pearson(xs: &[u64], ys: &[u64]) -> Option<f64> {
+    let n = xs.len().min(ys.len());
+    if n < 2 {
+        return None;
+    }
+
+    let mean_x = xs.iter().map(|x| *x as f64).sum::<f64>() / n as f64;
+    let mean_y = ys.iter().map(|y| *y as f64).sum::<f64>() / n as f64;
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys) {
+        let (dx, dy) = (*x as f64 - mean_x, *y as f64 - mean_y);
+        cov += dx * dy;
+        var_x += dx * dx;
+        var_y += dy * dy;
+    }
+
+    if var_x == 0.0 || var_y == 0.0 {
+        None
+    }
+    else {
+        Some(cov / (var_x.sqrt() * var_y.sqrt()))
+    }
+}
+
+// Collects every unknown header/entry field across any number of archives
+#[derive(Default)]
+pub struct VtPackUnknownFieldAnalyzer {
+    archive_count: usize,
+    fields: BTreeMap<String, FieldSamples>
+}
+
+impl VtPackUnknownFieldAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&mut self, name: &str, bits: u32, value: u64, correlates: &[(&'static str, u64)]) {
+        self.fields.entry(name.to_string()).or_default().push(bits, value, correlates);
+    }
+
+    pub fn add_file(&mut self, file: &VtPackFile) {
+        let raw = file.get_raw();
+        let header = &raw.header;
+        self.archive_count += 1;
+
+        let header_correlates = [
+            ("entry_count", header.entry_count as u64),
+            ("str_table_abs_offset", header.get_str_table_abs_offset()),
+            ("str_table_size", raw.str_table.table_size as u64)
+        ];
+        let (unk3, unk4, bits) = match header.version.get_layout() {
+            VtPackVersion::Ver1 => (header.unk3_v1 as u64, header.unk4_v1 as u64, 32),
+            _ => (header.unk3_v2, header.unk4_v2, 64)
+        };
+        self.push("header.unk1", 32, header.unk1 as u64, &header_correlates);
+        self.push("header.unk2", 32, header.unk2 as u64, &header_correlates);
+        self.push("header.unk1:unk2", 64, ((header.unk2 as u64) << 32) | header.unk1 as u64, &header_correlates);
+        self.push("header.unk3", bits, unk3, &header_correlates);
+        self.push("header.unk4", bits, unk4, &header_correlates);
+
+        for (entry_idx, entry) in raw.entries.iter().enumerate() {
+            let entry_correlates = [
+                ("file_size", entry.file_size),
+                ("entry_index", entry_idx as u64),
+                ("file_data_abs_offset", entry.file_data_abs_offset),
+                ("is_file", (entry.file_data_abs_offset != 0) as u64)
+            ];
+            self.push("entry.unk1", 32, entry.unk1 as u64, &entry_correlates);
+            self.push("entry.unk2", 64, entry.unk2, &entry_correlates);
+            self.push("entry.unk3", 32, entry.unk3 as u64, &entry_correlates);
+            self.push("entry.unk4", 32, entry.unk4 as u64, &entry_correlates);
+            // In case a 64-bit value (like a FILETIME) is split in two halves
+            self.push("entry.unk3:unk4", 64, ((entry.unk4 as u64) << 32) | entry.unk3 as u64, &entry_correlates);
+        }
+    }
+
+    pub fn get_archive_count(&self) -> usize {
+        self.archive_count
+    }
+
+    pub fn make_report(&self) -> Vec<VtPackFieldReport> {
+        self.fields.iter().map(|(name, samples)| samples.make_report(name)).collect()
+    }
+}
@@ -0,0 +1,138 @@
+use std::{fs::File, io::{self, Read, Seek, SeekFrom}, path::Path};
+use crate::{VtPackFile, VtPackProcessedEntry, VtPackEntryFilter, VtPackExtractOptions, VtPackExtractReport, VtPackEntryReader, VtPackReadOptions, VtPackError, Result, IoResultExt, reader::resolve_seek};
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+// This moves the file cursor too, but nothing here relies on it
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+// Reader with its own position over a shared file, so any number of them can be used at once
+pub struct VtPackPositionalReader<'a> {
+    file: &'a File,
+    size: u64,
+    pos: u64
+}
+
+impl<'a> VtPackPositionalReader<'a> {
+    pub fn new(file: &'a File) -> io::Result<Self> {
+        Ok(Self {
+            file,
+            size: file.metadata()?.len(),
+            pos: 0
+        })
+    }
+}
+
+impl Read for VtPackPositionalReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read_len = read_at(self.file, buf, self.pos)?;
+        self.pos += read_len as u64;
+        Ok(read_len)
+    }
+}
+
+impl Seek for VtPackPositionalReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = resolve_seek(pos, self.pos, self.size)?;
+        Ok(self.pos)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.pos)
+    }
+}
+
+// Unlike VtPackArchive, everything here takes &self, so it can be shared between threads
+pub struct VtPackSharedArchive {
+    f: File,
+    file: VtPackFile,
+    ignore_case: bool
+}
+
+impl VtPackSharedArchive {
+    pub fn new(f: File) -> Result<Self> {
+        Self::new_with_options(f, &VtPackReadOptions::default())
+    }
+
+    pub fn new_with_options(f: File, options: &VtPackReadOptions) -> Result<Self> {
+        let file = VtPackFile::new_with_options(&mut VtPackPositionalReader::new(&f)?, options)?;
+        Ok(Self {
+            f,
+            file,
+            ignore_case: false
+        })
+    }
+
+    pub fn open_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let f = File::open(path).with_path(path)?;
+        Self::new(f)
+    }
+
+    pub fn get_file(&self) -> &VtPackFile {
+        &self.file
+    }
+
+    pub fn get_file_mut(&mut self) -> &mut VtPackFile {
+        &mut self.file
+    }
+
+    pub fn entries(&self) -> &Vec<VtPackProcessedEntry> {
+        self.file.list_entries()
+    }
+
+    pub fn set_ignore_case(&mut self, ignore_case: bool) {
+        self.ignore_case = ignore_case;
+    }
+
+    pub fn make_reader(&self) -> Result<VtPackPositionalReader<'_>> {
+        Ok(VtPackPositionalReader::new(&self.f)?)
+    }
+
+    fn find_entry_index(&self, path: &str) -> Result<usize> {
+        self.file.find_index(path, self.ignore_case).ok_or_else(|| VtPackError::EntryNotFound(path.to_string()))
+    }
+
+    // The entry reader borrows a reader that only lives during the call
+    pub fn open_entry_with<T, F: FnOnce(&mut VtPackEntryReader<'_, VtPackPositionalReader<'_>>) -> Result<T>>(&self, entry: &VtPackProcessedEntry, f: F) -> Result<T> {
+        let mut reader = self.make_reader()?;
+        let mut entry_reader = self.file.open_entry(&mut reader, entry)?;
+        f(&mut entry_reader)
+    }
+
+    pub fn open_with<S: AsRef<str>, T, F: FnOnce(&mut VtPackEntryReader<'_, VtPackPositionalReader<'_>>) -> Result<T>>(&self, path: S, f: F) -> Result<T> {
+        let entry_idx = self.find_entry_index(path.as_ref())?;
+        self.open_entry_with(&self.file.list_entries()[entry_idx], f)
+    }
+
+    pub fn read_entry(&self, entry: &VtPackProcessedEntry) -> Result<Vec<u8>> {
+        self.open_entry_with(entry, |entry_reader| {
+            let mut data = Vec::with_capacity(entry_reader.get_size() as usize);
+            entry_reader.read_to_end(&mut data)?;
+            Ok(data)
+        })
+    }
+
+    pub fn read<S: AsRef<str>>(&self, path: S) -> Result<Vec<u8>> {
+        let entry_idx = self.find_entry_index(path.as_ref())?;
+        self.read_entry(&self.file.list_entries()[entry_idx])
+    }
+
+    pub fn extract_entry_to<P: AsRef<Path>>(&self, entry_idx: usize, out_path: P) -> Result<()> {
+        self.file.save_entry(&mut self.make_reader()?, &self.file.list_entries()[entry_idx], out_path)
+    }
+
+    pub fn extract_with_options<P: AsRef<Path>>(&self, out_path: P, filter: &VtPackEntryFilter, options: &VtPackExtractOptions) -> Result<VtPackExtractReport> {
+        self.file.export(&mut self.make_reader()?, out_path, filter, options)
+    }
+
+    pub fn extract_to<P: AsRef<Path>>(&self, out_path: P) -> Result<VtPackExtractReport> {
+        self.extract_with_options(out_path, &VtPackEntryFilter::new(), &VtPackExtractOptions::default())
+    }
+}
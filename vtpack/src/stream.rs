@@ -0,0 +1,336 @@
+use std::{collections::HashMap, io::{self, Cursor, Read}, path::Path};
+use binrw::{BinRead, Endian};
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use crate::{VtPackFile, VtPackProcessedEntry, VtPackRawHeader, VtPackVersion, VtPackEntryFilter, VtPackExtractOptions, VtPackExtractReport, VtPackCompression, VtPackReadOptions, VtPackError, Result, VTPACK_MAGIC, RAW_ENTRY_HEADER_SIZE, get_raw_header_size, compression::{SNIFF_SIZE, sniff_entry_compression, check_compression}};
+
+fn cut_short_error(what: String) -> VtPackError {
+    VtPackError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, format!("{} was cut short", what)))
+}
+
+fn metadata_overflow_error() -> VtPackError {
+    VtPackError::Io(io::Error::new(io::ErrorKind::InvalidData, "archive metadata goes past the largest possible offset"))
+}
+
+fn read_up_to<R: Read>(reader: &mut R, data: &mut Vec<u8>, size: u64) -> Result<()> {
+    let cur_size = data.len() as u64;
+    if size > cur_size {
+        reader.take(size - cur_size).read_to_end(data)?;
+        if (data.len() as u64) < size {
+            return Err(cut_short_error("archive metadata".to_string()));
+        }
+    }
+    Ok(())
+}
+
+// The callback also gets the size the data should have, returns how much stored data the entry took
+fn stream_entry_data<R: Read, F: FnOnce(&mut dyn Read, u64) -> Result<()>>(source: &mut R, entry: &VtPackProcessedEntry, raw_data: bool, f: F) -> Result<u64> {
+    let mut head = Vec::new();
+    let mut info = None;
+    if let Some((stored_size, _)) = entry.compressed_size_pair.filter(|_| !raw_data) {
+        source.by_ref().take(stored_size.min(SNIFF_SIZE)).read_to_end(&mut head)?;
+        info = sniff_entry_compression(entry.compressed_size_pair, &head).filter(|info| info.compression.is_supported());
+
+        // Compressions can only be confirmed with all the data, which has to be buffered to be read again
+        if let Some(sniffed_info) = info {
+            source.by_ref().take(stored_size - head.len() as u64).read_to_end(&mut head)?;
+            if !check_compression(&mut head.as_slice(), &sniffed_info)? {
+                info = None;
+            }
+        }
+    }
+
+    let data_size = info.map_or(entry.file_size as u64, |info| info.stored_size);
+    let mut rest = source.by_ref().take(data_size.saturating_sub(head.len() as u64));
+    {
+        let stored = Cursor::new(&head).chain(&mut rest);
+        let (mut data_reader, size): (Box<dyn Read + '_>, u64) = match info {
+            Some(info) => (match info.compression {
+                VtPackCompression::Zlib => Box::new(ZlibDecoder::new(stored)),
+                VtPackCompression::Deflate => Box::new(DeflateDecoder::new(stored)),
+                VtPackCompression::Gzip => Box::new(GzDecoder::new(stored)),
+                // Compressions that can't be decoded are never detected for reading
+                _ => Box::new(stored)
+            }, info.uncompressed_size),
+            None => (Box::new(stored), data_size)
+        };
+        f(&mut data_reader.by_ref().take(size), size)?;
+    }
+
+    // Whatever the callback left unread still needs to be skipped
+    io::copy(&mut rest, &mut io::sink())?;
+    if rest.limit() > 0 {
+        return Err(cut_short_error(format!("entry '{}' data", entry.path)));
+    }
+    Ok(data_size)
+}
+
+// Archives read from a stream that can't seek, like stdin or a decompression pipe
+// Only the metadata (header through the entry table) gets buffered, entry data is then read in a single pass in offset order
+pub struct VtPackStreamArchive<R: Read> {
+    reader: R,
+    file: VtPackFile,
+    metadata: Vec<u8>,
+    stream_pos: u64
+}
+
+impl<R: Read> VtPackStreamArchive<R> {
+    pub fn new(reader: R) -> Result<Self> {
+        Self::new_with_options(reader, &VtPackReadOptions::default())
+    }
+
+    pub fn new_with_options(mut reader: R, options: &VtPackReadOptions) -> Result<Self> {
+        let mut metadata = Vec::new();
+        read_up_to(&mut reader, &mut metadata, VTPACK_MAGIC.len() as u64 + 4)?;
+        let endian = VtPackRawHeader::detect_endian(&mut Cursor::new(&metadata))?;
+        let raw_version: [u8; 4] = metadata[VTPACK_MAGIC.len()..].try_into().unwrap();
+        let version = VtPackVersion::from_raw(match endian {
+            Endian::Big => u32::from_be_bytes(raw_version),
+            Endian::Little => u32::from_le_bytes(raw_version)
+        });
+
+        read_up_to(&mut reader, &mut metadata, get_raw_header_size(version))?;
+        let header = VtPackRawHeader::read_options(&mut Cursor::new(&metadata), endian, ())?;
+
+        let str_table_abs_offset = header.get_str_table_abs_offset();
+        let table_data_abs_offset = str_table_abs_offset.checked_add(4).ok_or_else(metadata_overflow_error)?;
+        read_up_to(&mut reader, &mut metadata, table_data_abs_offset)?;
+        let mut table_size_cursor = Cursor::new(&metadata);
+        table_size_cursor.set_position(str_table_abs_offset);
+        let table_size = u32::read_options(&mut table_size_cursor, endian, ())?;
+
+        let entries_end = table_data_abs_offset.checked_add(table_size as u64)
+            .and_then(|entries_abs_offset| entries_abs_offset.checked_add(header.entry_count as u64 * RAW_ENTRY_HEADER_SIZE))
+            .ok_or_else(metadata_overflow_error)?;
+        read_up_to(&mut reader, &mut metadata, entries_end)?;
+
+        let file = VtPackFile::new_with_options(&mut Cursor::new(&metadata), options)?;
+        let stream_pos = metadata.len() as u64;
+        Ok(Self {
+            reader,
+            file,
+            metadata,
+            stream_pos
+        })
+    }
+
+    pub fn get_file(&self) -> &VtPackFile {
+        &self.file
+    }
+
+    // Profiles or timestamp decoders can still be applied before reading any entries
+    pub fn get_file_mut(&mut self) -> &mut VtPackFile {
+        &mut self.file
+    }
+
+    pub fn entries(&self) -> &Vec<VtPackProcessedEntry> {
+        self.file.list_entries()
+    }
+
+    pub fn get_stream_pos(&self) -> u64 {
+        self.stream_pos
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn stream_entries<F: FnMut(usize, &VtPackProcessedEntry, &mut dyn Read, u64) -> Result<()>>(&mut self, mut entry_idxs: Vec<usize>, mut f: F) -> Result<()> {
+        let entries = self.file.list_entries();
+        entry_idxs.sort_by_key(|&entry_idx| (entries[entry_idx].file_data_abs_offset, entry_idx));
+
+        for entry_idx in entry_idxs {
+            let entry = &entries[entry_idx];
+            let offset = entry.file_data_abs_offset;
+            let data_end = offset.checked_add(entry.file_size as u64);
+
+            // Empty files often share their offset with the next entry, but they don't need any data anyway
+            if entry.file_size == 0 {
+                stream_entry_data(&mut io::empty(), entry, self.file.raw_data, |data_reader, size| f(entry_idx, entry, data_reader, size))?;
+                continue;
+            }
+
+            // Only possible with data placed before the string table, which is already buffered
+            if let Some(data_end) = data_end.filter(|&data_end| data_end <= self.metadata.len() as u64) {
+                stream_entry_data(&mut &self.metadata[offset as usize..data_end as usize], entry, self.file.raw_data, |data_reader, size| f(entry_idx, entry, data_reader, size))?;
+                continue;
+            }
+
+            // Data starting inside the metadata goes on past it: the buffered part is read first, as long as nothing after the metadata was consumed yet
+            let metadata_size = self.metadata.len() as u64;
+            if offset < metadata_size && self.stream_pos == metadata_size {
+                let buffered_data = &self.metadata[offset as usize..];
+                let data_size = stream_entry_data(&mut buffered_data.chain(self.reader.by_ref()), entry, self.file.raw_data, |data_reader, size| f(entry_idx, entry, data_reader, size))?;
+                self.stream_pos += data_size.saturating_sub(buffered_data.len() as u64);
+                continue;
+            }
+
+            if offset < self.stream_pos {
+                return Err(VtPackError::DataAlreadyPassed {
+                    path: entry.path.clone(),
+                    offset,
+                    stream_pos: self.stream_pos
+                });
+            }
+
+            let gap_size = offset - self.stream_pos;
+            let skipped_size = io::copy(&mut self.reader.by_ref().take(gap_size), &mut io::sink())?;
+            self.stream_pos += skipped_size;
+            if skipped_size != gap_size {
+                return Err(cut_short_error("archive data".to_string()));
+            }
+
+            let data_size = stream_entry_data(&mut self.reader, entry, self.file.raw_data, |data_reader, size| f(entry_idx, entry, data_reader, size))?;
+            self.stream_pos += data_size;
+        }
+
+        Ok(())
+    }
+
+    // Files are visited in data offset order, not in table order, and each one can only be visited once
+    pub fn for_each_entry<F: FnMut(&VtPackProcessedEntry, &mut dyn Read) -> Result<()>>(&mut self, filter: &VtPackEntryFilter, mut f: F) -> Result<()> {
+        let entry_idxs = self.file.list_entries().iter().enumerate().filter(|(_, entry)| entry.is_file() && filter.matches(entry)).map(|(entry_idx, _)| entry_idx).collect();
+        self.stream_entries(entry_idxs, |_, entry, data_reader, _| f(entry, data_reader))
+    }
+
+    pub fn extract_with_options<P: AsRef<Path>>(&mut self, out_path: P, filter: &VtPackEntryFilter, options: &VtPackExtractOptions) -> Result<VtPackExtractReport> {
+        let report = self.file.prepare_export(out_path.as_ref(), filter, options)?;
+        let out_paths: HashMap<usize, &Path> = report.entries.iter()
+            .filter(|extracted_entry| extracted_entry.writes_file())
+            .map(|extracted_entry| (extracted_entry.entry_index, extracted_entry.out_path.as_path()))
+            .collect();
+
+        self.stream_entries(out_paths.keys().copied().collect(), |entry_idx, entry, data_reader, size| {
+            if options.dry_run {
+                VtPackFile::check_entry_data(entry, data_reader, size)
+            }
+            else {
+                VtPackFile::write_entry_data(entry, data_reader, out_paths[&entry_idx], size)
+            }
+        })?;
+
+        Ok(report)
+    }
+
+    pub fn extract_to<P: AsRef<Path>>(&mut self, out_path: P) -> Result<VtPackExtractReport> {
+        self.extract_with_options(out_path, &VtPackEntryFilter::new(), &VtPackExtractOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use binrw::BinWrite;
+    use flate2::{Compression, write::ZlibEncoder};
+    use super::*;
+    use crate::tests::{make_test_archive, make_built_test_archive, make_test_out_dir, read_test_tree, list_test_report};
+
+    // Moves the data of b.bin to the given offset, keeping whatever bytes are already there
+    fn move_test_entry_data(archive: &mut Vec<u8>, offset: u64, size: u64) {
+        let file = VtPackFile::new(&mut Cursor::new(&*archive)).unwrap();
+        let mut raw = file.get_raw().clone();
+        raw.entries[2].file_data_abs_offset = offset;
+        raw.entries[2].file_size = size;
+        raw.entries[2].unk2 = size;
+        raw.write_options(&mut Cursor::new(archive), file.get_endian(), ()).unwrap();
+    }
+
+    fn check_stream_export(archive: &[u8], name: &str) {
+        let out_path = make_test_out_dir(name);
+        let stream_out_path = make_test_out_dir(&format!("{}-stream", name));
+        let file = VtPackFile::new(&mut Cursor::new(archive)).unwrap();
+        for dry_run in [false, true] {
+            let options = VtPackExtractOptions::new().clean_target(true).dry_run(dry_run);
+            let report = file.export(&mut Cursor::new(archive), &out_path, &VtPackEntryFilter::new(), &options).unwrap();
+            let stream_report = VtPackStreamArchive::new(archive).unwrap().extract_with_options(&stream_out_path, &VtPackEntryFilter::new(), &options).unwrap();
+            assert_eq!(list_test_report(&stream_report, &stream_out_path), list_test_report(&report, &out_path));
+            if !dry_run {
+                assert_eq!(read_test_tree(&stream_out_path), read_test_tree(&out_path));
+            }
+        }
+        std::fs::remove_dir_all(&out_path).unwrap();
+        std::fs::remove_dir_all(&stream_out_path).unwrap();
+    }
+
+    #[test]
+    fn stream_matches_export() {
+        for version in [VtPackVersion::Ver1, VtPackVersion::Ver2] {
+            for endian in [Endian::Little, Endian::Big] {
+                check_stream_export(&make_test_archive(version, endian), "stream");
+            }
+        }
+    }
+
+    #[test]
+    fn stream_data_inside_metadata() {
+        for version in [VtPackVersion::Ver1, VtPackVersion::Ver2] {
+            // Between the header and the string table
+            let mut archive = make_test_archive(version, Endian::Little);
+            move_test_entry_data(&mut archive, 0x30, 8);
+            check_stream_export(&archive, "stream-before-table");
+
+            // Starting in the entry table and going on past it
+            let entries_end = VtPackStreamArchive::new(archive.as_slice()).unwrap().get_stream_pos();
+            move_test_entry_data(&mut archive, entries_end - 4, 0x10);
+            check_stream_export(&archive, "stream-straddling");
+
+            let mut stream_archive = VtPackStreamArchive::new(archive.as_slice()).unwrap();
+            let mut data = Vec::new();
+            stream_archive.for_each_entry(&VtPackEntryFilter::new(), |entry, data_reader| {
+                if entry.get_path().ends_with("b.bin") {
+                    data_reader.read_to_end(&mut data)?;
+                }
+                Ok(())
+            }).unwrap();
+            assert_eq!(data, archive[entries_end as usize - 4..entries_end as usize + 0xC]);
+        }
+    }
+
+    #[test]
+    fn stream_cut_short() {
+        let archive = make_test_archive(VtPackVersion::Ver2, Endian::Little);
+        let out_path = make_test_out_dir("stream-cut-short");
+        let options = VtPackExtractOptions::new().dry_run(true);
+        for size in [0x10, 0x50, 0xD0, 0x104, 0x124] {
+            let res = VtPackStreamArchive::new(&archive[..size]).and_then(|mut stream_archive| stream_archive.extract_with_options(&out_path, &VtPackEntryFilter::new(), &options));
+            assert!(matches!(res, Err(VtPackError::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof), "{:#X}: {:?}", size, res);
+        }
+    }
+
+    #[test]
+    fn stream_metadata_overflow() {
+        let mut archive = make_test_archive(VtPackVersion::Ver2, Endian::Little);
+        let mut header = VtPackFile::new(&mut Cursor::new(&archive)).unwrap().get_raw().header.clone();
+        header.set_str_table_abs_offset(u64::MAX - 1);
+        header.write_options(&mut Cursor::new(&mut archive), Endian::Little, ()).unwrap();
+        assert!(matches!(VtPackStreamArchive::new(archive.as_slice()), Err(VtPackError::Io(ref err)) if err.kind() == io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn stream_compressed_entries() {
+        let plain_data: Vec<u8> = (0..0x4000).map(|i| (i % 13) as u8).collect();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&plain_data).unwrap();
+        let zlib_data = encoder.finish().unwrap();
+        let gzip_asset = [&[0x1F, 0x8B, 0x08, 0x00][..], &plain_data[..0x100]].concat();
+        let archive = make_built_test_archive(&[
+            ("compressed.bin", zlib_data.clone(), plain_data.len() as u64),
+            ("asset.gz", gzip_asset.clone(), 0x10),
+            ("plain.bin", plain_data.clone(), 0)
+        ]);
+        check_stream_export(&archive, "stream-compressed");
+
+        for (raw_data, compressed_data) in [(false, &plain_data), (true, &zlib_data)] {
+            let mut stream_archive = VtPackStreamArchive::new_with_options(archive.as_slice(), &VtPackReadOptions::new().raw_data(raw_data)).unwrap();
+            let mut entries_data = HashMap::new();
+            stream_archive.for_each_entry(&VtPackEntryFilter::new(), |entry, data_reader| {
+                let mut data = Vec::new();
+                data_reader.read_to_end(&mut data)?;
+                entries_data.insert(entry.get_path().clone(), data);
+                Ok(())
+            }).unwrap();
+            assert_eq!(&entries_data["compressed.bin"], compressed_data);
+            assert_eq!(entries_data["asset.gz"], gzip_asset);
+            assert_eq!(entries_data["plain.bin"], plain_data);
+        }
+    }
+}
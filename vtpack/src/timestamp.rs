@@ -0,0 +1,112 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::{VtPackFile, VtPackRawEntryHeader};
+
+// FILETIME values are 100ns intervals since 1601-01-01
+pub(crate) const FILETIME_TICKS_PER_SEC: u64 = 10_000_000;
+pub(crate) const FILETIME_UNIX_EPOCH: u64 = 11_644_473_600 * FILETIME_TICKS_PER_SEC;
+
+// Between 1990-01-01 and 2040-01-01
+pub(crate) const PLAUSIBLE_FILETIME_MIN: u64 = FILETIME_UNIX_EPOCH + 631_152_000 * FILETIME_TICKS_PER_SEC;
+pub(crate) const PLAUSIBLE_FILETIME_MAX: u64 = FILETIME_UNIX_EPOCH + 2_208_988_800 * FILETIME_TICKS_PER_SEC;
+
+pub fn is_plausible_filetime(value: u64) -> bool {
+    (PLAUSIBLE_FILETIME_MIN..PLAUSIBLE_FILETIME_MAX).contains(&value)
+}
+
+const NANOS_PER_FILETIME_TICK: u64 = 1_000_000_000 / FILETIME_TICKS_PER_SEC;
+
+fn filetime_ticks_to_duration(ticks: u64) -> Duration {
+    Duration::new(ticks / FILETIME_TICKS_PER_SEC, ((ticks % FILETIME_TICKS_PER_SEC) * NANOS_PER_FILETIME_TICK) as u32)
+}
+
+// None if the platform can't represent the time
+pub fn filetime_to_system_time(filetime: u64) -> Option<SystemTime> {
+    if filetime >= FILETIME_UNIX_EPOCH {
+        UNIX_EPOCH.checked_add(filetime_ticks_to_duration(filetime - FILETIME_UNIX_EPOCH))
+    }
+    else {
+        UNIX_EPOCH.checked_sub(filetime_ticks_to_duration(FILETIME_UNIX_EPOCH - filetime))
+    }
+}
+
+// None for times before 1601, or too far in the future
+pub fn system_time_to_filetime(time: SystemTime) -> Option<u64> {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => u64::try_from(since_epoch.as_nanos() / NANOS_PER_FILETIME_TICK as u128).ok()?.checked_add(FILETIME_UNIX_EPOCH),
+        Err(err) => FILETIME_UNIX_EPOCH.checked_sub(u64::try_from(err.duration().as_nanos() / NANOS_PER_FILETIME_TICK as u128).ok()?)
+    }
+}
+
+// Candidate locations of a timestamp in the entry headers, none of them is confirmed yet
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum VtPackTimestampField {
+    Unk2,
+    // unk3 holds the low half, unk4 the high half
+    Unk3Unk4
+}
+
+impl VtPackTimestampField {
+    pub fn get_value(&self, entry: &VtPackRawEntryHeader) -> u64 {
+        match self {
+            Self::Unk2 => entry.unk2,
+            Self::Unk3Unk4 => ((entry.unk4 as u64) << 32) | entry.unk3 as u64
+        }
+    }
+
+    pub fn set_value(&self, entry: &mut VtPackRawEntryHeader, value: u64) {
+        match self {
+            Self::Unk2 => entry.unk2 = value,
+            Self::Unk3Unk4 => {
+                entry.unk3 = value as u32;
+                entry.unk4 = (value >> 32) as u32;
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct VtPackTimestampDecoder {
+    field: VtPackTimestampField,
+    check_plausible: bool
+}
+
+impl VtPackTimestampDecoder {
+    pub fn new(field: VtPackTimestampField) -> Self {
+        Self {
+            field,
+            check_plausible: true
+        }
+    }
+
+    // Without the check, any nonzero value is decoded, however unlikely the resulting date is
+    pub fn check_plausible(mut self, check_plausible: bool) -> Self {
+        self.check_plausible = check_plausible;
+        self
+    }
+
+    pub fn get_field(&self) -> VtPackTimestampField {
+        self.field
+    }
+
+    pub fn decode(&self, entry: &VtPackRawEntryHeader) -> Option<SystemTime> {
+        let filetime = self.field.get_value(entry);
+        if filetime == 0 || (self.check_plausible && !is_plausible_filetime(filetime)) {
+            return None;
+        }
+        filetime_to_system_time(filetime)
+    }
+}
+
+impl VtPackFile {
+    // Timestamps are only decoded if explicitly requested
+    pub fn set_timestamp_decoder(&mut self, decoder: Option<VtPackTimestampDecoder>) {
+        self.timestamp_decoder = decoder;
+        for (p_entry, entry) in self.p_entries.iter_mut().zip(self.raw.entries.iter()) {
+            p_entry.modified_time = decoder.and_then(|decoder| decoder.decode(entry));
+        }
+    }
+
+    pub fn get_timestamp_decoder(&self) -> Option<VtPackTimestampDecoder> {
+        self.timestamp_decoder
+    }
+}
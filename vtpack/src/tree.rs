@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use crate::{VtPackFile, path::split_path_components};
+
+pub const TREE_ROOT_NODE_INDEX: usize = 0;
+
+struct VtPackTreeNodeData {
+    name: String,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    entry_index: Option<usize>,
+    is_file: bool,
+    file_size: u64,
+    total_size: u64,
+    total_file_count: usize
+}
+
+pub struct VtPackTree {
+    nodes: Vec<VtPackTreeNodeData>,
+    // (parent node, child name) -> child node
+    child_index: HashMap<(usize, String), usize>
+}
+
+#[derive(Copy, Clone)]
+pub struct VtPackTreeNode<'a> {
+    tree: &'a VtPackTree,
+    index: usize
+}
+
+impl VtPackTree {
+    fn add_node(&mut self, parent: usize, name: &str) -> usize {
+        let key = (parent, name.to_string());
+        if let Some(index) = self.child_index.get(&key) {
+            return *index;
+        }
+
+        let index = self.nodes.len();
+        self.nodes.push(VtPackTreeNodeData {
+            name: name.to_string(),
+            parent: Some(parent),
+            children: Vec::new(),
+            entry_index: None,
+            is_file: false,
+            file_size: 0,
+            total_size: 0,
+            total_file_count: 0
+        });
+        self.nodes[parent].children.push(index);
+        self.child_index.insert(key, index);
+        index
+    }
+
+    pub fn new(file: &VtPackFile) -> Self {
+        let mut tree = Self {
+            nodes: vec![VtPackTreeNodeData {
+                name: String::new(),
+                parent: None,
+                children: Vec::new(),
+                entry_index: None,
+                is_file: false,
+                file_size: 0,
+                total_size: 0,
+                total_file_count: 0
+            }],
+            child_index: HashMap::new()
+        };
+
+        for (entry_idx, entry) in file.list_entries().iter().enumerate() {
+            let mut cur_node = TREE_ROOT_NODE_INDEX;
+            for comp in split_path_components(entry.get_raw_dir()).chain(split_path_components(entry.get_raw_name())) {
+                cur_node = tree.add_node(cur_node, comp);
+            }
+
+            // Entries without any path components (or duplicate paths) don't get a node of their own
+            let node = &mut tree.nodes[cur_node];
+            if cur_node != TREE_ROOT_NODE_INDEX && node.entry_index.is_none() {
+                node.entry_index = Some(entry_idx);
+                node.is_file = entry.is_file();
+                node.file_size = if entry.is_file() { entry.get_file_size() as u64 } else { 0 };
+            }
+        }
+
+        // Children are always created after their parents, so a reverse pass accumulates totals bottom-up
+        for index in (0..tree.nodes.len()).rev() {
+            let node = &mut tree.nodes[index];
+            if node.is_file {
+                node.total_size = node.file_size;
+                node.total_file_count = 1;
+            }
+
+            let (total_size, total_file_count) = (node.total_size, node.total_file_count);
+            if let Some(parent) = node.parent {
+                tree.nodes[parent].total_size += total_size;
+                tree.nodes[parent].total_file_count += total_file_count;
+            }
+        }
+
+        tree
+    }
+
+    pub fn root(&self) -> VtPackTreeNode<'_> {
+        self.get_node(TREE_ROOT_NODE_INDEX).unwrap()
+    }
+
+    pub fn get_node(&self, index: usize) -> Option<VtPackTreeNode<'_>> {
+        (index < self.nodes.len()).then_some(VtPackTreeNode {
+            tree: self,
+            index
+        })
+    }
+
+    pub fn get_node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn find<S: AsRef<str>>(&self, path: S) -> Option<VtPackTreeNode<'_>> {
+        let mut cur_node = TREE_ROOT_NODE_INDEX;
+        for comp in split_path_components(path.as_ref()) {
+            cur_node = *self.child_index.get(&(cur_node, comp.to_string()))?;
+        }
+        self.get_node(cur_node)
+    }
+}
+
+impl<'a> VtPackTreeNode<'a> {
+    fn data(&self) -> &'a VtPackTreeNodeData {
+        &self.tree.nodes[self.index]
+    }
+
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get_name(&self) -> &'a String {
+        &self.data().name
+    }
+
+    pub fn get_path(&self) -> String {
+        let mut comps = Vec::new();
+        let mut cur_node = Some(*self);
+        while let Some(node) = cur_node {
+            if node.index != TREE_ROOT_NODE_INDEX {
+                comps.push(node.get_name().as_str());
+            }
+            cur_node = node.parent();
+        }
+        comps.reverse();
+        comps.join(std::path::MAIN_SEPARATOR_STR)
+    }
+
+    // Index into VtPackFile::list_entries(), if any entry describes this node (directories may only be implied by their children)
+    pub fn get_entry_index(&self) -> Option<usize> {
+        self.data().entry_index
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.index == TREE_ROOT_NODE_INDEX
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.data().is_file
+    }
+
+    pub fn is_dir(&self) -> bool {
+        !self.data().is_file
+    }
+
+    pub fn get_file_size(&self) -> u64 {
+        self.data().file_size
+    }
+
+    pub fn get_total_size(&self) -> u64 {
+        self.data().total_size
+    }
+
+    pub fn get_total_file_count(&self) -> usize {
+        self.data().total_file_count
+    }
+
+    pub fn parent(&self) -> Option<VtPackTreeNode<'a>> {
+        self.data().parent.and_then(|parent| self.tree.get_node(parent))
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = VtPackTreeNode<'a>> + 'a {
+        let tree = self.tree;
+        self.data().children.iter().map(move |index| VtPackTreeNode {
+            tree,
+            index: *index
+        })
+    }
+
+    pub fn get_child<S: AsRef<str>>(&self, name: S) -> Option<VtPackTreeNode<'a>> {
+        let index = self.tree.child_index.get(&(self.index, name.as_ref().to_string()))?;
+        self.tree.get_node(*index)
+    }
+
+    // Pre-order walk over this node and all its descendants
+    pub fn walk(&self) -> VtPackTreeWalk<'a> {
+        VtPackTreeWalk {
+            tree: self.tree,
+            stack: vec![self.index]
+        }
+    }
+}
+
+pub struct VtPackTreeWalk<'a> {
+    tree: &'a VtPackTree,
+    stack: Vec<usize>
+}
+
+impl<'a> Iterator for VtPackTreeWalk<'a> {
+    type Item = VtPackTreeNode<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.stack.pop()?;
+        self.stack.extend(self.tree.nodes[index].children.iter().rev());
+        self.tree.get_node(index)
+    }
+}
@@ -0,0 +1,243 @@
+use std::{collections::HashMap, fmt, io::{Read, Seek, SeekFrom}};
+use binrw::BinRead;
+use crate::{VtPackFile, VtPackVersion, VtPackRawHeader, VtPackStringTable, VtPackRawEntryHeader, VtPackError, Result, RAW_ENTRY_HEADER_SIZE, read_table_string, path::make_lookup_key, compression::{get_stored_size, get_compressed_size_pair}};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum VtPackProblem {
+    UnknownVersion {
+        version: VtPackVersion
+    },
+    StringTableOverlapsHeader {
+        str_table_abs_offset: u64,
+        header_size: u64
+    },
+    StringTableOutOfBounds {
+        str_table_abs_offset: u64,
+        str_table_size: u64,
+        archive_size: u64
+    },
+    ImpossibleEntryCount {
+        entry_count: u32,
+        max_entry_count: u64
+    },
+    InvalidStringOffset {
+        entry_index: usize,
+        offset: u32
+    },
+    UnterminatedString {
+        entry_index: usize,
+        offset: u32
+    },
+    EntryOutOfBounds {
+        entry_index: usize,
+        offset: u64,
+        size: u64,
+        archive_size: u64
+    },
+    DataOverlapsHeader {
+        entry_index: usize
+    },
+    StringTableOverlapsData {
+        entry_index: usize
+    },
+    EntryTableOverlapsData {
+        entry_index: usize
+    },
+    OverlappingData {
+        entry_index: usize,
+        other_entry_index: usize
+    },
+    DuplicatePath {
+        entry_index: usize,
+        other_entry_index: usize,
+        path: String
+    }
+}
+
+impl fmt::Display for VtPackProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownVersion { version } => write!(f, "version {:#X} is unknown, the rest was checked with the v{} layout", version.get_raw(), version.get_layout()),
+            Self::StringTableOverlapsHeader { str_table_abs_offset, header_size } => write!(f, "string table at {:#X} overlaps the header (size {:#X})", str_table_abs_offset, header_size),
+            Self::StringTableOutOfBounds { str_table_abs_offset, str_table_size, archive_size } => write!(f, "string table at {:#X} (size {:#X}) runs past the end of the archive (size {:#X})", str_table_abs_offset, str_table_size, archive_size),
+            Self::ImpossibleEntryCount { entry_count, max_entry_count } => write!(f, "entry count {} is impossible for the archive size (at most {} entries fit)", entry_count, max_entry_count),
+            Self::InvalidStringOffset { entry_index, offset } => write!(f, "entry #{}: string table offset {:#X} is out of bounds", entry_index, offset),
+            Self::UnterminatedString { entry_index, offset } => write!(f, "entry #{}: string at string table offset {:#X} is not NUL-terminated", entry_index, offset),
+            Self::EntryOutOfBounds { entry_index, offset, size, archive_size } => write!(f, "entry #{}: data at {:#X} (size {:#X}) runs past the end of the archive (size {:#X})", entry_index, offset, size, archive_size),
+            Self::DataOverlapsHeader { entry_index } => write!(f, "entry #{}: data overlaps the header", entry_index),
+            Self::StringTableOverlapsData { entry_index } => write!(f, "entry #{}: data overlaps the string table", entry_index),
+            Self::EntryTableOverlapsData { entry_index } => write!(f, "entry #{}: data overlaps the entry table", entry_index),
+            Self::OverlappingData { entry_index, other_entry_index } => write!(f, "entry #{}: data overlaps the data of entry #{}", entry_index, other_entry_index),
+            Self::DuplicatePath { entry_index, other_entry_index, path } => write!(f, "entry #{}: path '{}' is already used by entry #{}", entry_index, path, other_entry_index)
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VtPackValidationReport {
+    entry_count: usize,
+    problems: Vec<VtPackProblem>
+}
+
+impl VtPackValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn get_problems(&self) -> &Vec<VtPackProblem> {
+        &self.problems
+    }
+
+    // Number of entries that could actually be checked
+    pub fn get_entry_count(&self) -> usize {
+        self.entry_count
+    }
+}
+
+// Empty ranges still count as overlapping if they start inside the other range
+fn ranges_overlap(start: u64, end: u64, other_start: u64, other_end: u64) -> bool {
+    if start == end {
+        start >= other_start && start < other_end
+    }
+    else {
+        start < other_end && end > other_start
+    }
+}
+
+struct MetadataLayout {
+    header_size: u64,
+    str_table_start: u64,
+    entries_start: u64,
+    entries_end: u64
+}
+
+impl VtPackFile {
+    fn validate_entries<R: Seek + Read>(reader: &mut R, report: &mut VtPackValidationReport, str_table: &VtPackStringTable, entries: &[VtPackRawEntryHeader], layout: &MetadataLayout, archive_size: u64) -> Result<()> {
+        // Duplicates are checked the way the game would look them up, case-insensitively
+        let mut paths: HashMap<String, usize> = HashMap::new();
+        let mut data_ranges: Vec<(u64, u64, usize)> = Vec::new();
+
+        for (entry_idx, entry) in entries.iter().enumerate() {
+            let mut path_strs = Vec::new();
+            for offset in [entry.path_dir_str_table_offset, entry.path_name_str_table_offset] {
+                match read_table_string(&str_table.table_data, offset) {
+                    Ok(path_str) => path_strs.push(path_str),
+                    Err(VtPackError::InvalidStringOffset(offset)) => report.problems.push(VtPackProblem::InvalidStringOffset { entry_index: entry_idx, offset }),
+                    Err(_) => report.problems.push(VtPackProblem::UnterminatedString { entry_index: entry_idx, offset })
+                }
+            }
+
+            if let [dir_str, name_str] = path_strs.as_slice() {
+                let path = format!("{}\\{}", dir_str, name_str);
+                if let Some(other_entry_idx) = paths.get(&make_lookup_key(&path, true)) {
+                    report.problems.push(VtPackProblem::DuplicatePath {
+                        entry_index: entry_idx,
+                        other_entry_index: *other_entry_idx,
+                        path: make_lookup_key(&path, false)
+                    });
+                }
+                else {
+                    paths.insert(make_lookup_key(&path, true), entry_idx);
+                }
+            }
+
+            // Directories have no data
+            if entry.file_data_abs_offset == 0 {
+                continue;
+            }
+
+            // Compressed entries only take the smaller size of their pair
+            let offset = entry.file_data_abs_offset;
+            let (size, _) = get_stored_size(reader, offset, entry.file_size, get_compressed_size_pair(entry.file_size, entry.unk2), archive_size)?;
+            match offset.checked_add(size) {
+                Some(end) if end <= archive_size => {
+                    if ranges_overlap(offset, end, 0, layout.header_size) {
+                        report.problems.push(VtPackProblem::DataOverlapsHeader { entry_index: entry_idx });
+                    }
+                    if ranges_overlap(offset, end, layout.str_table_start, layout.entries_start) {
+                        report.problems.push(VtPackProblem::StringTableOverlapsData { entry_index: entry_idx });
+                    }
+                    if ranges_overlap(offset, end, layout.entries_start, layout.entries_end) {
+                        report.problems.push(VtPackProblem::EntryTableOverlapsData { entry_index: entry_idx });
+                    }
+                    if size > 0 {
+                        data_ranges.push((offset, end, entry_idx));
+                    }
+                }
+                _ => report.problems.push(VtPackProblem::EntryOutOfBounds { entry_index: entry_idx, offset, size, archive_size })
+            }
+        }
+
+        data_ranges.sort();
+        let mut furthest: Option<(u64, usize)> = None;
+        for (start, end, entry_idx) in data_ranges {
+            match furthest {
+                Some((furthest_end, furthest_entry_idx)) if start < furthest_end => {
+                    report.problems.push(VtPackProblem::OverlappingData { entry_index: entry_idx, other_entry_index: furthest_entry_idx });
+                    if end > furthest_end {
+                        furthest = Some((end, entry_idx));
+                    }
+                }
+                _ => furthest = Some((end, entry_idx))
+            }
+        }
+
+        Ok(())
+    }
+
+    // Problems found in the archive are reported, errors are only returned if the archive can't be read at all
+    pub fn validate<R: Seek + Read>(reader: &mut R) -> Result<VtPackValidationReport> {
+        let mut report = VtPackValidationReport::default();
+        let archive_size = reader.seek(SeekFrom::End(0))?;
+        reader.rewind()?;
+
+        let endian = VtPackRawHeader::detect_endian(reader)?;
+        let header = VtPackRawHeader::read_options(reader, endian, ())?;
+        if !header.version.is_known() {
+            report.problems.push(VtPackProblem::UnknownVersion { version: header.version });
+        }
+        let header_size = header.get_size();
+        let str_table_abs_offset = header.get_str_table_abs_offset();
+        if str_table_abs_offset < header_size {
+            report.problems.push(VtPackProblem::StringTableOverlapsHeader { str_table_abs_offset, header_size });
+        }
+
+        let str_table_end = str_table_abs_offset.checked_add(4);
+        if str_table_end.is_none_or(|end| end > archive_size) {
+            report.problems.push(VtPackProblem::StringTableOutOfBounds { str_table_abs_offset, str_table_size: 4, archive_size });
+            return Ok(report);
+        }
+
+        reader.seek(SeekFrom::Start(str_table_abs_offset))?;
+        let str_table_size = 4 + u32::read_options(reader, endian, ())? as u64;
+        if str_table_abs_offset + str_table_size > archive_size {
+            report.problems.push(VtPackProblem::StringTableOutOfBounds { str_table_abs_offset, str_table_size, archive_size });
+            return Ok(report);
+        }
+
+        reader.seek(SeekFrom::Start(str_table_abs_offset))?;
+        let str_table = VtPackStringTable::read_options(reader, endian, ())?;
+
+        let entries_abs_offset = header.get_entries_abs_offset(&str_table);
+        let max_entry_count = (archive_size - entries_abs_offset) / RAW_ENTRY_HEADER_SIZE;
+        if header.entry_count as u64 > max_entry_count {
+            report.problems.push(VtPackProblem::ImpossibleEntryCount { entry_count: header.entry_count, max_entry_count });
+            return Ok(report);
+        }
+
+        let mut entries = Vec::with_capacity(header.entry_count as usize);
+        for _ in 0..header.entry_count {
+            entries.push(VtPackRawEntryHeader::read_options(reader, endian, ())?);
+        }
+        report.entry_count = entries.len();
+
+        let layout = MetadataLayout {
+            header_size,
+            str_table_start: str_table_abs_offset,
+            entries_start: entries_abs_offset,
+            entries_end: entries_abs_offset + entries.len() as u64 * RAW_ENTRY_HEADER_SIZE
+        };
+        Self::validate_entries(reader, &mut report, &str_table, &entries, &layout, archive_size)?;
+        Ok(report)
+    }
+}